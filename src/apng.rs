@@ -0,0 +1,910 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::colour::ColourChunk;
+use crate::image::{ColourType, Image, ImageError, ImageHeader};
+use crate::palette::{Palette, Transparency};
+use crate::png::Png;
+use crate::{Error, Result};
+
+const ANIMATION_CONTROL: &str = "acTL";
+const FRAME_CONTROL: &str = "fcTL";
+const FRAME_DATA: &str = "fdAT";
+
+/// An acTL chunk: how many frames the animation has and how many times it
+/// plays, where 0 means forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub frames: u32,
+    pub plays: u32,
+}
+
+impl AnimationControl {
+    pub fn as_chunk(&self) -> Chunk {
+        let data = [self.frames.to_be_bytes(), self.plays.to_be_bytes()].concat();
+        Chunk::new(ChunkType::from_str(ANIMATION_CONTROL).unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        if data.len() != 8 {
+            return Err(Box::new(ApngError::Length(ANIMATION_CONTROL, data.len())));
+        }
+        Ok(AnimationControl { frames: u32_at(data, 0), plays: u32_at(data, 4) })
+    }
+}
+
+/// What happens to a frame's region before the next frame is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    /// Leave it as it is
+    None,
+    /// Clear it to transparent black
+    Background,
+    /// Restore what was there before the frame
+    Previous,
+}
+
+/// How a frame is drawn over what is already there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    /// Replace the region, alpha included
+    Source,
+    /// Alpha-composite over the region
+    Over,
+}
+
+/// An fcTL chunk: the region, timing and compositing of one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_numerator: u16,
+    /// 0 means hundredths of a second
+    pub delay_denominator: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    pub fn delay_seconds(&self) -> f64 {
+        let denominator = if self.delay_denominator == 0 { 100 } else { self.delay_denominator };
+        self.delay_numerator as f64 / denominator as f64
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        let mut data = vec![];
+        for value in [self.sequence_number, self.width, self.height, self.x_offset, self.y_offset] {
+            data.extend(value.to_be_bytes());
+        }
+        data.extend(self.delay_numerator.to_be_bytes());
+        data.extend(self.delay_denominator.to_be_bytes());
+        data.push(self.dispose_op as u8);
+        data.push(self.blend_op as u8);
+        Chunk::new(ChunkType::from_str(FRAME_CONTROL).unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        if data.len() != 26 {
+            return Err(Box::new(ApngError::Length(FRAME_CONTROL, data.len())));
+        }
+        let dispose_op = match data[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            op => return Err(Box::new(ApngError::Operation("dispose", op))),
+        };
+        let blend_op = match data[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            op => return Err(Box::new(ApngError::Operation("blend", op))),
+        };
+        Ok(FrameControl {
+            sequence_number: u32_at(data, 0),
+            width: u32_at(data, 4),
+            height: u32_at(data, 8),
+            x_offset: u32_at(data, 12),
+            y_offset: u32_at(data, 16),
+            delay_numerator: u16::from_be_bytes([data[20], data[21]]),
+            delay_denominator: u16::from_be_bytes([data[22], data[23]]),
+            dispose_op,
+            blend_op,
+        })
+    }
+}
+
+/// An fdAT chunk: a sequence number followed by data laid out like IDAT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameData {
+    pub sequence_number: u32,
+    pub data: Vec<u8>,
+}
+
+impl FrameData {
+    pub fn as_chunk(&self) -> Chunk {
+        let data = [self.sequence_number.to_be_bytes().as_slice(), &self.data].concat();
+        Chunk::new(ChunkType::from_str(FRAME_DATA).unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameData {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        if data.len() < 4 {
+            return Err(Box::new(ApngError::Length(FRAME_DATA, data.len())));
+        }
+        Ok(FrameData { sequence_number: u32_at(data, 0), data: data[4..].to_vec() })
+    }
+}
+
+/// One frame of an animation: its fcTL and the chunks holding its image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub control: FrameControl,
+    /// Index of the fcTL chunk
+    pub index: usize,
+    /// Indices of the IDAT or fdAT chunks
+    pub data_chunks: Vec<usize>,
+    /// The frame is the IDAT image, which decoders without APNG support show
+    pub default_image: bool,
+}
+
+fn u32_at(data: &[u8], i: usize) -> u32 {
+    u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]])
+}
+
+// Whether a frame's region lies inside the image
+fn fits(header: &ImageHeader, c: &FrameControl) -> bool {
+    c.width > 0
+        && c.height > 0
+        && c.x_offset as u64 + c.width as u64 <= header.width as u64
+        && c.y_offset as u64 + c.height as u64 <= header.height as u64
+}
+
+fn is_sequenced(chunk: &Chunk) -> bool {
+    let chunk_type = chunk.chunk_type().to_string();
+    chunk_type == FRAME_CONTROL || chunk_type == FRAME_DATA
+}
+
+/// Whether a file has any animation chunks.
+pub fn is_animated(png: &Png) -> bool {
+    png.chunks().iter().any(|c| is_sequenced(c) || c.chunk_type().to_string() == ANIMATION_CONTROL)
+}
+
+/// The file's acTL chunk, if it has one.
+pub fn animation_control(png: &Png) -> Result<Option<AnimationControl>> {
+    match png.chunk_by_type(ANIMATION_CONTROL) {
+        Some(chunk) => Ok(Some(AnimationControl::try_from(chunk)?)),
+        None => Ok(None),
+    }
+}
+
+/// The frames of an animation in file order. Each fcTL starts a frame, which
+/// takes the IDAT or fdAT chunks up to the next fcTL.
+pub fn frames(png: &Png) -> Result<Vec<Frame>> {
+    let mut frames: Vec<Frame> = vec![];
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        match chunk.chunk_type().to_string().as_str() {
+            FRAME_CONTROL => frames.push(Frame { control: FrameControl::try_from(chunk)?, index, data_chunks: vec![], default_image: false }),
+            "IDAT" => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.data_chunks.push(index);
+                    frame.default_image = true;
+                }
+            },
+            FRAME_DATA => match frames.last_mut() {
+                Some(frame) if !frame.default_image => frame.data_chunks.push(index),
+                _ => return Err(Box::new(ApngError::StrayData(index))),
+            },
+            _ => {},
+        }
+    }
+    Ok(frames)
+}
+
+/// Builds a standalone PNG of one frame without decoding it: an IHDR with the
+/// frame's size, the chunks other than animation chunks that come before the
+/// image data, and the frame's IDAT chunks or its fdAT chunks turned back into
+/// IDAT by dropping their sequence numbers.
+pub fn frame_png(png: &Png, frame: &Frame) -> Result<Png> {
+    let mut header = ImageHeader::from_png(png)?;
+    header.width = frame.control.width;
+    header.height = frame.control.height;
+    let is_data = |c: &Chunk| c.chunk_type().to_string() == "IDAT" || c.chunk_type().to_string() == FRAME_DATA;
+    let first_data = png.chunks().iter().position(is_data).unwrap_or(png.chunks().len());
+
+    let mut chunks = vec![header.as_chunk()];
+    let kept = png.chunks()[..first_data].iter().filter(|c| {
+        let chunk_type = c.chunk_type().to_string();
+        chunk_type != ImageHeader::CHUNK_TYPE && chunk_type != ANIMATION_CONTROL && chunk_type != FRAME_CONTROL
+    });
+    chunks.extend(kept.cloned());
+    for &index in &frame.data_chunks {
+        let chunk = &png.chunks()[index];
+        let data = match frame.default_image {
+            true => chunk.data().to_vec(),
+            false => FrameData::try_from(chunk)?.data,
+        };
+        chunks.push(Chunk::new(ChunkType::from_str("IDAT").unwrap(), data));
+    }
+    chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+    renumber(&mut chunks);
+    Ok(Png::from_chunks(chunks))
+}
+
+/// Draws the frames in turn onto a canvas the size of the image, following
+/// their blend and dispose operations, and returns the canvas as each frame
+/// leaves it. The results are RGBA, 16 bits deep if the file is and 8
+/// otherwise, and keep the file's colour space chunks.
+pub fn composite_frames(png: &Png) -> Result<Vec<Png>> {
+    let header = ImageHeader::from_png(png)?;
+    let depth = if header.bit_depth == 16 { 16 } else { 8 };
+    let palette = Palette::from_png(png)?;
+    let transparency = Transparency::from_png(png, &header)?;
+    let colour_chunks: Vec<&Chunk> = png.chunks().iter().filter(|c| ColourChunk::CHUNK_TYPES.contains(&c.chunk_type().to_string().as_str())).collect();
+
+    let width = header.width as usize;
+    let canvas_len = match width.checked_mul(header.height as usize).and_then(|n| n.checked_mul(4)) {
+        Some(n) => n,
+        None => return Err(Box::new(ImageError::TooLarge(header.width, header.height))),
+    };
+    let mut canvas = vec![0u16; canvas_len];
+    let mut composited = vec![];
+    for (number, frame) in frames(png)?.iter().enumerate() {
+        let c = &frame.control;
+        if !fits(&header, c) {
+            return Err(Box::new(ApngError::OutOfBounds(number)));
+        }
+        let image = Image::decode(&frame_png(png, frame)?)?;
+        let pixels = rgba(&image, palette.as_ref(), transparency.as_ref(), depth);
+        // The first frame has nothing before it to restore
+        let dispose_op = match (number, c.dispose_op) {
+            (0, DisposeOp::Previous) => DisposeOp::Background,
+            (_, op) => op,
+        };
+        let previous = (dispose_op == DisposeOp::Previous).then(|| canvas.clone());
+
+        let region = |x: usize, y: usize| ((c.y_offset as usize + y) * width + c.x_offset as usize + x) * 4;
+        for y in 0..c.height as usize {
+            for x in 0..c.width as usize {
+                let source = &pixels[(y * c.width as usize + x) * 4..][..4];
+                let target = &mut canvas[region(x, y)..][..4];
+                match c.blend_op {
+                    BlendOp::Source => target.copy_from_slice(source),
+                    BlendOp::Over => blend_over(source, target, depth),
+                }
+            }
+        }
+
+        let mut output = Image::new(header.width, header.height, depth, ColourType::TruecolourAlpha, canvas.clone())?.to_png();
+        for chunk in colour_chunks.iter().rev() {
+            output.insert_chunk(1, (*chunk).clone())?;
+        }
+        composited.push(output);
+
+        match (dispose_op, previous) {
+            (DisposeOp::Previous, Some(previous)) => canvas = previous,
+            (DisposeOp::Background, _) => {
+                for y in 0..c.height as usize {
+                    canvas[region(0, y)..region(c.width as usize, y)].fill(0);
+                }
+            },
+            _ => {},
+        }
+    }
+    Ok(composited)
+}
+
+// An image's pixels as RGBA samples `depth` bits deep, which is 8 for indexed images
+fn rgba(image: &Image, palette: Option<&Palette>, transparency: Option<&Transparency>, depth: u8) -> Vec<u16> {
+    let header = image.header();
+    let source_max = (1u32 << header.bit_depth) - 1;
+    let max = (1u32 << depth) - 1;
+    let scale = |sample: u16| ((sample as u32 * max + source_max / 2) / source_max) as u16;
+    let opaque = max as u16;
+    let channels = header.colour_type.channels();
+    image
+        .samples()
+        .chunks(channels)
+        .flat_map(|p| match header.colour_type {
+            ColourType::Greyscale => {
+                let alpha = if transparency == Some(&Transparency::Greyscale(p[0])) { 0 } else { opaque };
+                [scale(p[0]), scale(p[0]), scale(p[0]), alpha]
+            },
+            ColourType::Truecolour => {
+                let alpha = if transparency == Some(&Transparency::Truecolour([p[0], p[1], p[2]])) { 0 } else { opaque };
+                [scale(p[0]), scale(p[1]), scale(p[2]), alpha]
+            },
+            ColourType::Indexed => {
+                let [r, g, b] = palette.and_then(|palette| palette.entries().get(p[0] as usize)).copied().unwrap_or([0; 3]);
+                let alpha = transparency.map_or(255, |t| t.alpha(p[0] as usize));
+                [r as u16, g as u16, b as u16, alpha as u16]
+            },
+            ColourType::GreyscaleAlpha => [scale(p[0]), scale(p[0]), scale(p[0]), scale(p[1])],
+            ColourType::TruecolourAlpha => [scale(p[0]), scale(p[1]), scale(p[2]), scale(p[3])],
+        })
+        .collect()
+}
+
+// Alpha-composites one RGBA pixel over another
+fn blend_over(source: &[u16], target: &mut [u16], depth: u8) {
+    let max = ((1u32 << depth) - 1) as f64;
+    let source_alpha = source[3] as f64 / max;
+    let target_alpha = target[3] as f64 / max * (1.0 - source_alpha);
+    let alpha = source_alpha + target_alpha;
+    if alpha == 0.0 {
+        target.fill(0);
+        return;
+    }
+    for i in 0..3 {
+        target[i] = ((source[i] as f64 * source_alpha + target[i] as f64 * target_alpha) / alpha).round() as u16;
+    }
+    target[3] = (alpha * max).round() as u16;
+}
+
+/// Builds an animation with one frame per image, each shown for `delay`
+/// seconds as a numerator and denominator. The first image is also the
+/// default image and keeps its other chunks; the rest must share its bit
+/// depth, colour type, interlacing and palette, and be no larger. None may be
+/// animated already. Smaller frames are drawn at the top-left corner and
+/// replace what is under them, leaving the rest of the previous frame showing.
+pub fn assemble(images: &[Png], delay: (u16, u16), plays: u32) -> Result<Png> {
+    let first = match images.first() {
+        Some(png) => png,
+        None => return Err(Box::new(ApngError::NoFrames)),
+    };
+    let canvas = ImageHeader::from_png(first)?;
+    let palette = |png: &Png| [Palette::CHUNK_TYPE, Transparency::CHUNK_TYPE].map(|t| png.chunk_by_type(t).map(|c| c.data().to_vec()));
+    let mut controls = vec![];
+    for (number, png) in images.iter().enumerate() {
+        if is_animated(png) {
+            return Err(Box::new(ApngError::Animated(number)));
+        }
+        let header = ImageHeader::from_png(png)?;
+        let problem = if (header.bit_depth, header.colour_type) != (canvas.bit_depth, canvas.colour_type) {
+            Some("bit depth or colour type")
+        } else if header.interlaced != canvas.interlaced {
+            Some("interlacing")
+        } else if header.width > canvas.width || header.height > canvas.height {
+            Some("size")
+        } else if palette(png) != palette(first) {
+            Some("palette")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            return Err(Box::new(ApngError::Incompatible(number, problem)));
+        }
+        controls.push(FrameControl {
+            sequence_number: 0,
+            width: header.width,
+            height: header.height,
+            x_offset: 0,
+            y_offset: 0,
+            delay_numerator: delay.0,
+            delay_denominator: delay.1,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        });
+    }
+
+    // Sequence numbers are filled in at the end, once every chunk is in place
+    let is_data = |c: &Chunk| c.chunk_type().to_string() == "IDAT";
+    let mut chunks: Vec<Chunk> = vec![];
+    for chunk in first.chunks().iter().filter(|c| !is_sequenced(c) && c.chunk_type().to_string() != ANIMATION_CONTROL) {
+        match chunk.chunk_type().to_string().as_str() {
+            "IDAT" if !chunks.iter().any(is_data) => chunks.push(controls[0].as_chunk()),
+            "IEND" => break,
+            _ => {},
+        }
+        chunks.push(chunk.clone());
+        if chunk.chunk_type().to_string() == ImageHeader::CHUNK_TYPE {
+            chunks.push(AnimationControl { frames: images.len() as u32, plays }.as_chunk());
+        }
+    }
+
+    // The other frames go straight after the default image
+    let mut position = match chunks.iter().rposition(is_data) {
+        Some(i) => i + 1,
+        None => return Err(Box::new(ImageError::MissingData)),
+    };
+    for (number, png) in images.iter().enumerate().skip(1) {
+        let mut frame_chunks = vec![controls[number].as_chunk()];
+        for chunk in png.chunks().iter().filter(|c| is_data(c)) {
+            frame_chunks.push(FrameData { sequence_number: 0, data: chunk.data().to_vec() }.as_chunk());
+        }
+        if frame_chunks.len() == 1 {
+            return Err(Box::new(ImageError::MissingData));
+        }
+        let count = frame_chunks.len();
+        chunks.splice(position..position, frame_chunks);
+        position += count;
+    }
+    chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+    renumber(&mut chunks);
+    Ok(Png::from_chunks(chunks))
+}
+
+/// Sets the sequence numbers of the fcTL and fdAT chunks to 0, 1, 2... in
+/// file order and the acTL frame count to the number of fcTL chunks, so that
+/// inserting, removing or moving chunks keeps an animation consistent. Nothing
+/// is renumbered while an fdAT chunk is outside a frame, as the numbers are
+/// then all that is left to show where the frames were.
+pub fn renumber(chunks: &mut [Chunk]) {
+    if stray_data(chunks).is_some() {
+        return;
+    }
+    let (mut next, mut frames) = (0u32, 0u32);
+    for chunk in chunks.iter_mut().filter(|c| is_sequenced(c)) {
+        if chunk.data().len() >= 4 && chunk.data()[..4] != next.to_be_bytes() {
+            let mut data = chunk.data().to_vec();
+            data[..4].copy_from_slice(&next.to_be_bytes());
+            *chunk = Chunk::new(chunk.chunk_type().clone(), data);
+        }
+        if chunk.chunk_type().to_string() == FRAME_CONTROL {
+            frames += 1;
+        }
+        next += 1;
+    }
+    let control = chunks.iter_mut().find(|c| c.chunk_type().to_string() == ANIMATION_CONTROL);
+    if let Some(chunk) = control {
+        if frames > 0 && chunk.data().len() == 8 && chunk.data()[..4] != frames.to_be_bytes() {
+            let mut data = chunk.data().to_vec();
+            data[..4].copy_from_slice(&frames.to_be_bytes());
+            *chunk = Chunk::new(chunk.chunk_type().clone(), data);
+        }
+    }
+}
+
+// The index of the first fdAT chunk that does not belong to a frame of its own
+fn stray_data(chunks: &[Chunk]) -> Option<usize> {
+    let mut in_frame = false;
+    for (index, chunk) in chunks.iter().enumerate() {
+        match chunk.chunk_type().to_string().as_str() {
+            FRAME_CONTROL => in_frame = true,
+            "IDAT" => in_frame = false,
+            FRAME_DATA if !in_frame => return Some(index),
+            _ => {},
+        }
+    }
+    None
+}
+
+// The data of each frame's fdAT chunks, without their sequence numbers
+fn frame_contents(chunks: &[Chunk]) -> Vec<Vec<&[u8]>> {
+    let mut contents: Vec<Vec<&[u8]>> = vec![];
+    for chunk in chunks {
+        match chunk.chunk_type().to_string().as_str() {
+            FRAME_CONTROL => contents.push(vec![]),
+            FRAME_DATA => if let Some(frame) = contents.last_mut() {
+                frame.push(chunk.data().get(4..).unwrap_or_default());
+            },
+            _ => {},
+        }
+    }
+    contents
+}
+
+/// The ways an edit from `before` to `after` damages an animation: dropping an
+/// fcTL chunk but not its fdAT chunks, which would merge them into the frame
+/// before, or leaving an fdAT chunk outside a frame. Whole frames may go.
+pub fn edit_problems(before: &[Chunk], after: &[Chunk]) -> Vec<String> {
+    let count = |chunks: &[Chunk]| chunks.iter().filter(|c| c.chunk_type().to_string() == FRAME_CONTROL).count();
+    let (old_frames, new_frames) = (frame_contents(before), frame_contents(after));
+    let mut problems = vec![];
+    if count(after) < count(before) && new_frames.iter().any(|frame| !old_frames.contains(frame)) {
+        problems.push(String::from("an fcTL chunk would be removed without the frame data that follows it"));
+    }
+    if let (None, Some(index)) = (stray_data(before), stray_data(after)) {
+        problems.push(format!("the fdAT chunk at index {} would not follow an fcTL chunk for a new frame", index));
+    }
+    problems
+}
+
+/// Problems with a file's animation chunks: chunks that do not parse, a frame
+/// count that does not match, broken sequence numbers, frames without data
+/// and frames that do not fit the image.
+pub fn apng_problems(png: &Png) -> Vec<String> {
+    if !is_animated(png) {
+        return vec![];
+    }
+    let mut problems = vec![];
+    let control = match animation_control(png) {
+        Ok(Some(c)) => Some(c),
+        Ok(None) => {
+            problems.push(String::from("there are fcTL or fdAT chunks but no acTL chunk"));
+            None
+        },
+        Err(e) => {
+            problems.push(e.to_string());
+            None
+        },
+    };
+
+    let sequenced = png.chunks().iter().enumerate().filter(|(_, c)| is_sequenced(c));
+    for (expected, (index, chunk)) in sequenced.enumerate() {
+        let found = match chunk.chunk_type().to_string().as_str() {
+            FRAME_DATA => FrameData::try_from(chunk).map(|d| d.sequence_number),
+            _ => FrameControl::try_from(chunk).map(|c| c.sequence_number),
+        };
+        let found = match found {
+            Ok(n) => n,
+            Err(e) => {
+                problems.push(format!("chunk {}: {}", index, e));
+                continue;
+            },
+        };
+        if found as usize != expected {
+            problems.push(format!("chunk {} ({}) has sequence number {} where {} was expected", index, chunk.chunk_type(), found, expected));
+            break;
+        }
+    }
+
+    let frames = match frames(png) {
+        Ok(f) => f,
+        Err(e) => {
+            problems.push(e.to_string());
+            return problems;
+        },
+    };
+    if let Some(control) = control {
+        if control.frames == 0 {
+            problems.push(String::from("acTL declares no frames"));
+        } else if control.frames as usize != frames.len() {
+            problems.push(format!("acTL declares {} frames but there are {} fcTL chunks", control.frames, frames.len()));
+        }
+    }
+    let header = ImageHeader::from_png(png).ok();
+    for (number, frame) in frames.iter().enumerate() {
+        let c = &frame.control;
+        if frame.data_chunks.is_empty() {
+            problems.push(format!("frame {} has no image data", number));
+        }
+        if let Some(h) = &header {
+            if !fits(h, c) {
+                problems.push(format!("frame {} does not fit in the {}x{} image", number, h.width, h.height));
+            } else if frame.default_image && (c.x_offset, c.y_offset, c.width, c.height) != (0, 0, h.width, h.height) {
+                problems.push(format!("frame {} is the default image but does not cover all of it", number));
+            }
+        }
+    }
+    problems
+}
+
+/// One frame as `print` shows it.
+#[derive(Serialize)]
+pub struct FrameEntry {
+    pub frame: usize,
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_seconds: f64,
+    pub dispose: String,
+    pub blend: String,
+    pub data_type: String,
+    pub data_chunks: usize,
+}
+
+/// The acTL values and the frames of an animation, as `print` shows them.
+#[derive(Serialize)]
+pub struct AnimationSummary {
+    pub declared_frames: u32,
+    pub plays: u32,
+    pub frames: Vec<FrameEntry>,
+}
+
+/// Summarizes a file's animation, if it has one that can be read.
+pub fn animation_summary(png: &Png) -> Result<Option<AnimationSummary>> {
+    let control = match animation_control(png)? {
+        Some(c) => c,
+        None => return Ok(None),
+    };
+    let frames = frames(png)?
+        .iter()
+        .enumerate()
+        .map(|(number, frame)| {
+            let c = &frame.control;
+            FrameEntry {
+                frame: number,
+                sequence_number: c.sequence_number,
+                width: c.width,
+                height: c.height,
+                x_offset: c.x_offset,
+                y_offset: c.y_offset,
+                delay_seconds: c.delay_seconds(),
+                dispose: c.dispose_op.to_string(),
+                blend: c.blend_op.to_string(),
+                data_type: String::from(if frame.default_image { "IDAT" } else { FRAME_DATA }),
+                data_chunks: frame.data_chunks.len(),
+            }
+        })
+        .collect();
+    Ok(Some(AnimationSummary { declared_frames: control.frames, plays: control.plays, frames }))
+}
+
+impl Display for DisposeOp {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let text = match self {
+            DisposeOp::None => "none",
+            DisposeOp::Background => "background",
+            DisposeOp::Previous => "previous",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl Display for BlendOp {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let text = match self {
+            BlendOp::Source => "source",
+            BlendOp::Over => "over",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+
+
+#[derive(Debug)]
+pub enum ApngError {
+    Length(&'static str, usize),
+    Operation(&'static str, u8),
+    StrayData(usize),
+    OutOfBounds(usize),
+    NoFrames,
+    Incompatible(usize, &'static str),
+    Animated(usize),
+}
+impl Display for ApngError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ApngError::Length(chunk_type, n) => write!(f, "{} bytes is the wrong length for a {} chunk.", n, chunk_type),
+            ApngError::Operation(kind, op) => write!(f, "{} is not a valid fcTL {} operation.", op, kind),
+            ApngError::StrayData(index) => write!(f, "The fdAT chunk at index {} does not follow an fcTL chunk for a new frame.", index),
+            ApngError::OutOfBounds(frame) => write!(f, "Frame {} does not fit in the image.", frame),
+            ApngError::NoFrames => write!(f, "An animation needs at least one frame."),
+            ApngError::Incompatible(frame, what) => write!(f, "Frame {} does not match the first frame's {}.", frame, what),
+            ApngError::Animated(frame) => write!(f, "Frame {} is already animated; extract its frames first.", frame),
+        }
+    }
+}
+impl std::error::Error for ApngError {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ColourType, Image};
+
+    fn frame_control(sequence_number: u32, width: u32, height: u32) -> FrameControl {
+        FrameControl {
+            sequence_number,
+            width,
+            height,
+            x_offset: 0,
+            y_offset: 0,
+            delay_numerator: 1,
+            delay_denominator: 10,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        }
+    }
+
+    // A 2x2 animation whose first frame is the default image
+    fn animated_png() -> Png {
+        let image = Image::new(2, 2, 8, ColourType::Truecolour, vec![0; 12]).unwrap();
+        let mut chunks = image.to_png().chunks().to_vec();
+        let idat = chunks[1].clone();
+        chunks.insert(1, AnimationControl { frames: 2, plays: 0 }.as_chunk());
+        chunks.insert(2, frame_control(0, 2, 2).as_chunk());
+        chunks.insert(4, frame_control(1, 1, 1).as_chunk());
+        chunks.insert(5, FrameData { sequence_number: 2, data: idat.data().to_vec() }.as_chunk());
+        Png::from_chunks(chunks)
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|c| c.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let control = frame_control(7, 3, 4);
+        let chunk = control.as_chunk();
+        assert_eq!(chunk.data().len(), 26);
+        assert_eq!(FrameControl::try_from(&chunk).unwrap(), control);
+        assert_eq!(control.delay_seconds(), 0.1);
+
+        let animation = AnimationControl { frames: 3, plays: 1 };
+        assert_eq!(AnimationControl::try_from(&animation.as_chunk()).unwrap(), animation);
+        let data = FrameData { sequence_number: 9, data: vec![1, 2, 3] };
+        assert_eq!(FrameData::try_from(&data.as_chunk()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_invalid_frame_control() {
+        let mut data = frame_control(0, 1, 1).as_chunk().data().to_vec();
+        data[24] = 3;
+        assert!(FrameControl::try_from(&Chunk::new(ChunkType::from_str("fcTL").unwrap(), data)).is_err());
+        assert!(FrameData::try_from(&Chunk::new(ChunkType::from_str("fdAT").unwrap(), vec![0, 0])).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let png = animated_png();
+        assert_eq!(types(&png), ["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "IEND"]);
+        let frames = frames(&png).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].default_image);
+        assert_eq!(frames[0].data_chunks, [3]);
+        assert!(!frames[1].default_image);
+        assert_eq!((frames[1].index, frames[1].data_chunks.clone()), (4, vec![5]));
+        assert!(apng_problems(&png).is_empty());
+    }
+
+    #[test]
+    fn test_sequence_problems() {
+        let mut chunks = animated_png().chunks().to_vec();
+        chunks[5] = FrameData { sequence_number: 5, data: vec![] }.as_chunk();
+        let problems = apng_problems(&Png::from_chunks(chunks.clone()));
+        assert_eq!(problems, ["chunk 5 (fdAT) has sequence number 5 where 2 was expected"]);
+
+        chunks[1] = AnimationControl { frames: 3, plays: 0 }.as_chunk();
+        chunks[4] = frame_control(1, 3, 1).as_chunk();
+        let problems = apng_problems(&Png::from_chunks(chunks));
+        assert!(problems.contains(&String::from("acTL declares 3 frames but there are 2 fcTL chunks")));
+        assert!(problems.contains(&String::from("frame 1 does not fit in the 2x2 image")));
+    }
+
+    #[test]
+    fn test_edits_renumber() {
+        let mut png = animated_png();
+        png.insert_chunk(6, frame_control(0, 1, 1).as_chunk()).unwrap();
+        png.insert_chunk(7, FrameData { sequence_number: 0, data: vec![] }.as_chunk()).unwrap();
+        let numbers: Vec<u32> = png.chunks().iter().filter(|c| is_sequenced(c)).map(|c| u32_at(c.data(), 0)).collect();
+        assert_eq!(numbers, [0, 1, 2, 3, 4]);
+        assert_eq!(animation_control(&png).unwrap().unwrap().frames, 3);
+
+        png.remove_chunks_at(&[6, 7]).unwrap();
+        assert_eq!(types(&png), ["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "IEND"]);
+        assert!(apng_problems(&png).is_empty());
+    }
+
+    #[test]
+    fn test_edits_keep_frames() {
+        let mut png = animated_png();
+        assert!(png.remove_chunk_at(4).is_err());
+        assert!(png.move_chunk(5, 3).is_err());
+        assert_eq!(types(&png), types(&animated_png()));
+        png.insert_chunk(6, frame_control(0, 1, 1).as_chunk()).unwrap();
+        png.insert_chunk(7, FrameData { sequence_number: 0, data: vec![1] }.as_chunk()).unwrap();
+        assert!(png.remove_chunk_at(6).is_err());
+        png.remove_selected(&"^f".parse().unwrap()).unwrap();
+        assert_eq!(types(&png), ["IHDR", "acTL", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_frame_png() {
+        let png = animated_png();
+        let frames = frames(&png).unwrap();
+        let second = frame_png(&png, &frames[1]).unwrap();
+        assert_eq!(types(&second), ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(second.chunks()[1].data(), &png.chunks()[5].data()[4..]);
+        let header = ImageHeader::from_png(&second).unwrap();
+        assert_eq!((header.width, header.height), (1, 1));
+        assert_eq!(types(&frame_png(&png, &frames[0]).unwrap()), ["IHDR", "IDAT", "IEND"]);
+    }
+
+    // A 2x2 RGBA image with a 1x1 frame drawn over its bottom right pixel
+    fn overlaid_png(dispose_op: DisposeOp, blend_op: BlendOp) -> Png {
+        let background = Image::new(2, 2, 8, ColourType::TruecolourAlpha, [255, 0, 0, 255].repeat(4)).unwrap();
+        let overlay = Image::new(1, 1, 8, ColourType::TruecolourAlpha, vec![0, 0, 255, 51]).unwrap();
+        let mut chunks = background.to_png().chunks().to_vec();
+        chunks.insert(1, AnimationControl { frames: 3, plays: 0 }.as_chunk());
+        chunks.insert(2, frame_control(0, 2, 2).as_chunk());
+        let overlay_control = FrameControl { x_offset: 1, y_offset: 1, dispose_op, blend_op, ..frame_control(1, 1, 1) };
+        chunks.insert(4, overlay_control.as_chunk());
+        chunks.insert(5, FrameData { sequence_number: 2, data: overlay.compress() }.as_chunk());
+        chunks.insert(6, frame_control(3, 1, 1).as_chunk());
+        chunks.insert(7, FrameData { sequence_number: 4, data: overlay.compress() }.as_chunk());
+        Png::from_chunks(chunks)
+    }
+
+    fn pixel(png: &Png, x: usize, y: usize) -> Vec<u16> {
+        let image = Image::decode(png).unwrap();
+        image.samples()[(y * 2 + x) * 4..][..4].to_vec()
+    }
+
+    #[test]
+    fn test_composite_blend() {
+        let png = overlaid_png(DisposeOp::None, BlendOp::Over);
+        assert!(apng_problems(&png).is_empty());
+        let composited = composite_frames(&png).unwrap();
+        assert_eq!(composited.len(), 3);
+        assert_eq!(pixel(&composited[0], 1, 1), [255, 0, 0, 255]);
+        assert_eq!(pixel(&composited[1], 1, 1), [204, 0, 51, 255]);
+        assert_eq!(pixel(&composited[1], 0, 0), [255, 0, 0, 255]);
+
+        let replaced = composite_frames(&overlaid_png(DisposeOp::None, BlendOp::Source)).unwrap();
+        assert_eq!(pixel(&replaced[1], 1, 1), [0, 0, 255, 51]);
+    }
+
+    #[test]
+    fn test_composite_dispose() {
+        let cleared = composite_frames(&overlaid_png(DisposeOp::Background, BlendOp::Over)).unwrap();
+        assert_eq!(pixel(&cleared[2], 1, 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(&cleared[2], 0, 0), [0, 0, 255, 51]);
+        let restored = composite_frames(&overlaid_png(DisposeOp::Previous, BlendOp::Over)).unwrap();
+        assert_eq!(pixel(&restored[2], 1, 1), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_assemble() {
+        let still = |grey: u16| Image::new(2, 2, 8, ColourType::Greyscale, vec![grey; 4]).unwrap().to_png();
+        let mut first = still(0);
+        first.insert_chunk(1, Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0, 0, 177, 143])).unwrap();
+        let images = [first, still(128), still(255)];
+        let animation = assemble(&images, (1, 25), 2).unwrap();
+        assert_eq!(types(&animation), ["IHDR", "acTL", "gAMA", "fcTL", "IDAT", "fcTL", "fdAT", "fcTL", "fdAT", "IEND"]);
+        assert!(apng_problems(&animation).is_empty());
+        assert_eq!(animation_control(&animation).unwrap().unwrap(), AnimationControl { frames: 3, plays: 2 });
+
+        let frames = frames(&animation).unwrap();
+        assert_eq!(frames[2].control.sequence_number, 3);
+        assert_eq!(frames[2].control.delay_seconds(), 0.04);
+        let last = Image::decode(&frame_png(&animation, &frames[2]).unwrap()).unwrap();
+        assert_eq!(last.samples(), &[255; 4]);
+    }
+
+    #[test]
+    fn test_assemble_incompatible() {
+        let grey = Image::new(2, 2, 8, ColourType::Greyscale, vec![0; 4]).unwrap().to_png();
+        let larger = Image::new(3, 2, 8, ColourType::Greyscale, vec![0; 6]).unwrap().to_png();
+        let deeper = Image::new(2, 2, 16, ColourType::Greyscale, vec![0; 4]).unwrap().to_png();
+        assert!(assemble(&[grey, larger], (1, 10), 0).is_err());
+        let grey = Image::new(2, 2, 8, ColourType::Greyscale, vec![0; 4]).unwrap().to_png();
+        assert!(assemble(&[grey, deeper], (1, 10), 0).is_err());
+        assert!(assemble(&[], (1, 10), 0).is_err());
+        let grey = Image::new(2, 2, 8, ColourType::Greyscale, vec![0; 4]).unwrap().to_png();
+        assert!(assemble(&[grey, animated_png()], (1, 10), 0).is_err());
+    }
+
+    #[test]
+    fn test_assemble_smaller_frame() {
+        let first = Image::new(2, 2, 8, ColourType::Greyscale, vec![0; 4]).unwrap().to_png();
+        let smaller = Image::new(1, 1, 8, ColourType::Greyscale, vec![255]).unwrap().to_png();
+        let animation = assemble(&[first, smaller], (1, 10), 0).unwrap();
+        let control = &frames(&animation).unwrap()[1].control;
+        assert_eq!((control.width, control.height, control.x_offset, control.y_offset), (1, 1, 0, 0));
+        assert_eq!((control.dispose_op, control.blend_op), (DisposeOp::None, BlendOp::Source));
+        let last = &composite_frames(&animation).unwrap()[1];
+        assert_eq!(Image::decode(last).unwrap().samples(), &[255, 255, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_animation_summary() {
+        let summary = animation_summary(&animated_png()).unwrap().unwrap();
+        assert_eq!((summary.declared_frames, summary.plays), (2, 0));
+        assert_eq!(summary.frames[0].data_type, "IDAT");
+        assert_eq!(summary.frames[1].data_type, "fdAT");
+        assert_eq!(summary.frames[1].dispose, "none");
+        assert!(animation_summary(&Image::new(1, 1, 8, ColourType::Greyscale, vec![0]).unwrap().to_png()).unwrap().is_none());
+    }
+}
@@ -1,153 +1,697 @@
-use std::{fmt::{Display, Formatter}};
-use crate::{Error, Result};
-
-pub fn process_args(args: &[String]) -> Result<PngMeArgs> {
-    if args.len() < 2 { 
-        return Err(Box::new(ArgsError::NotEnoughArgs(args.len())));
-    }
-    if let Some(command) = args.get(1) {
-        let command = command.to_lowercase();
-        match command.as_str() {
-            "encode" => {
-                let encode_args = EncodeArgs::new(&args[1..]);
-                match encode_args {
-                    Err(e) => return Err(e),
-                    Ok(o) => return Ok(PngMeArgs::Encode(o)),
-                }
-            },
-            "decode" => {
-                let decode_args = DecodeArgs::new(&args[1..]);
-                match decode_args {
-                    Err(e) => return Err(e),
-                    Ok(o) => return Ok(PngMeArgs::Decode(o)),
-                }
-            },
-            "remove" => {
-                let remove_args = RemoveArgs::new(&args[1..]);
-                match remove_args {
-                    Err(e) => return Err(e),
-                    Ok(o) => return Ok(PngMeArgs::Remove(o)),
-                }
-            },
-            "print" => {
-                let print_args = PrintArgs::new(&args[1..]);
-                match print_args {
-                    Err(e) => return Err(e),
-                    Ok(o) => return Ok(PngMeArgs::Print(o)),
-                }
-            },
-            _ => return Err(Box::new(ArgsError::InvalidCommand(command))),
-        };
+use clap::{ArgGroup, Args, Parser, Subcommand};
 
-    }
-    return Err(Box::new(ArgsError::NoCommand));
-}
+use crate::colour::RenderingIntent;
+use crate::exif::{self, TagGroup};
+use crate::report::OutputFormat;
+use crate::select::ChunkSelector;
+use crate::strip::Preset;
 
-#[derive(Debug)]
-pub enum ArgsError {
-    NoCommand,
-    InvalidCommand(String),
-    NotEnoughArgs(usize),
-    TooManyArgs(usize),
-}
-impl Display for ArgsError {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        match self {
-            ArgsError::NoCommand => write!(f, "You did not provide a command."),
-            ArgsError::InvalidCommand(c) => write!(f, "{} is not a valid command.", c),
-            ArgsError::NotEnoughArgs(n) => write!(f, "You only provided {} arguments, which is not enough.", n),
-            ArgsError::TooManyArgs(n) => write!(f, "You provided {} arguments, which is too many.", n),
-        }
-    }
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  Success
+  1  Any other failure, including an edit that would break the file
+  2  Usage error, including an invalid chunk type
+  3  A file could not be read or written
+  4  The input is not a valid PNG file, or its image data is corrupt
+  5  The requested chunk or message was not found
+  6  Some files in a batch failed, or every input was skipped";
+
+const SELECTOR_HELP: &str = "\
+Chunks to select. A selector is a chunk index, a chunk type, a glob on the
+type such as r???, or one of the properties critical, ancillary, public,
+private, safe-to-copy and unsafe-to-copy. Join terms with commas to require
+all of them, e.g. private,unsafe-to-copy. A regular expression on the type
+starts with ^ or re: and takes up the rest of the selector, e.g. ^[a-z].";
+
+/// Hide messages in PNG files and inspect their chunks.
+///
+/// Anywhere a file is read or written, `-` stands for standard input or
+/// standard output.
+#[derive(Parser)]
+#[command(name = "png_me", version, after_help = EXIT_CODES)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: PngMeArgs,
 }
-impl std::error::Error for ArgsError {}
 
+#[derive(Subcommand)]
 pub enum PngMeArgs {
+    /// Append a message chunk to PNG files
     Encode(EncodeArgs),
+    /// Show the message stored in a chunk
     Decode(DecodeArgs),
+    /// Remove a chunk
     Remove(RemoveArgs),
+    /// List the chunks of PNG files
     Print(PrintArgs),
+    /// Show one chunk's type bits, fields and a hex dump of its data
+    Inspect(InspectArgs),
+    /// Compare the chunks and pixels of two PNG files
+    Diff(DiffArgs),
+    /// Look for signs of LSB steganography in the pixel data
+    Analyze(AnalyzeArgs),
+    /// Write the data of selected chunks to files
+    Extract(ExtractArgs),
+    /// Insert a chunk holding the contents of a file
+    Import(ImportArgs),
+    /// Give a chunk new data, keeping its position
+    Replace(ReplaceArgs),
+    /// Change the type of a chunk, keeping its data
+    Rename(RenameArgs),
+    /// Move a chunk to another position
+    Move(MoveArgs),
+    /// Copy ancillary chunks from one PNG file to another
+    Transplant(TransplantArgs),
+    /// Remove metadata chunks before publishing images
+    Strip(StripArgs),
+    /// Check CRCs, chunk types and chunk ordering
+    Validate(ValidateArgs),
+    /// Explain what the letters of a chunk type mean
+    Explain(ExplainArgs),
+    /// Show or edit the palette of an image
+    Palette(PaletteArgs),
+    /// Show or set the colour space chunks of an image, and report conflicts
+    Colour(ColourArgs),
+    /// List the EXIF tags of an image, or remove some of them
+    Exif(ExifArgs),
+    /// Work with the frames of animated PNGs
+    #[command(subcommand)]
+    Frames(FramesArgs),
 }
 
+/// How editing commands treat the tIME chunk.
+#[derive(Args)]
+pub struct TimeArgs {
+    /// Set tIME to the current time, adding the chunk if there is none
+    #[arg(long, conflicts_with = "preserve_time")]
+    pub update_time: bool,
+    /// Leave tIME untouched; otherwise an existing tIME is set to the current time
+    #[arg(long)]
+    pub preserve_time: bool,
+}
+
+#[derive(Args)]
 pub struct EncodeArgs {
-    pub path: String,
-    pub chunk_type: String,
+    /// PNG files, glob patterns or directories
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<String>,
+    /// Four letter type of the new chunk, e.g. ruSt
+    #[arg(short = 't', long, required_unless_present_any = ["auto_type", "palette"], conflicts_with_all = ["auto_type", "palette"])]
+    pub chunk_type: Option<String>,
+    /// Pick a valid private, ancillary, safe-to-copy type instead
+    #[arg(long, conflicts_with = "palette")]
+    pub auto_type: bool,
+    /// Derive the automatic type from this key, so `decode --key` can find it
+    #[arg(long, conflicts_with_all = ["chunk_type", "palette"])]
+    pub key: Option<String>,
+    /// Hide the message in the order of an indexed image's palette instead of
+    /// in a chunk; the image looks the same, and `palette` shows the capacity
+    #[arg(long)]
+    pub palette: bool,
+    /// Allow invalid, critical or registered chunk types
+    #[arg(long)]
+    pub force: bool,
+    /// Message to store in the chunk
+    #[arg(short, long)]
     pub message: String,
+    /// Write the result here instead of back to the input file
+    #[arg(short, long = "output", value_name = "FILE")]
     pub output_file: Option<String>,
+    /// Descend into directories, processing every .png file
+    #[arg(short, long)]
+    pub recursive: bool,
+    #[command(flatten)]
+    pub time: TimeArgs,
 }
-impl EncodeArgs {
-    pub fn new(args: &[String]) -> Result<Self> {
-        let args_length = args.len();
-        if args_length < 3 {
-            return Err(Box::new(ArgsError::NotEnoughArgs(args_length)));
-        } else if args_length > 5 {
-            return Err(Box::new(ArgsError::TooManyArgs(args_length)));
-        } 
-        let output_file = if args_length == 3 { None } else { Some(args[3].clone()) };
 
-        Ok(Self {
-            path: args[1].clone(),
-            chunk_type: args[2].clone(),
-            message: args[3].clone(),
-            output_file: output_file,
-        })
-    }
+#[derive(Args)]
+pub struct DecodeArgs {
+    /// PNG files, glob patterns or directories
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<String>,
+    /// Type of the chunk holding the message, or a selector such as r???
+    #[arg(short = 't', long, value_name = "SELECTOR", long_help = SELECTOR_HELP, required_unless_present_any = ["key", "palette"], conflicts_with_all = ["key", "palette"])]
+    pub chunk_type: Option<ChunkSelector>,
+    /// Look in the chunk type `encode --auto-type --key` derived from this key
+    #[arg(long, conflicts_with = "palette")]
+    pub key: Option<String>,
+    /// Read a message hidden by `encode --palette`
+    #[arg(long, conflicts_with = "all")]
+    pub palette: bool,
+    /// Show the message in every selected chunk, with its chunk index
+    #[arg(short, long)]
+    pub all: bool,
+    /// Descend into directories, processing every .png file
+    #[arg(short, long)]
+    pub recursive: bool,
 }
 
-pub struct DecodeArgs {
+#[derive(Args)]
+pub struct RemoveArgs {
+    /// PNG files, glob patterns or directories
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<String>,
+    /// Type of the chunk to remove, or a selector; the first match is removed
+    #[arg(short = 't', long, value_name = "SELECTOR", long_help = SELECTOR_HELP, required_unless_present = "index", conflicts_with = "index")]
+    pub chunk_type: Option<ChunkSelector>,
+    /// Remove every selected chunk rather than the first
+    #[arg(short, long, requires = "chunk_type")]
+    pub all: bool,
+    /// Remove the chunk at this index instead, as listed by `print`
+    #[arg(short, long, value_name = "N")]
+    pub index: Option<usize>,
+    /// Descend into directories, processing every .png file
+    #[arg(short, long)]
+    pub recursive: bool,
+    #[command(flatten)]
+    pub time: TimeArgs,
+}
+
+#[derive(Args)]
+pub struct PrintArgs {
+    /// PNG files, glob patterns or directories
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<String>,
+    /// How to lay out the chunk list. With several files, JSON is written one
+    /// object per line and YAML documents are separated by ---
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+    /// Also list the palette entries with their transparency
+    #[arg(long)]
+    pub palette: bool,
+    /// Descend into directories, processing every .png file
+    #[arg(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Args)]
+pub struct InspectArgs {
+    /// PNG file
+    pub path: String,
+    /// Chunk index, chunk type or selector picking the chunks to inspect
+    #[arg(long_help = SELECTOR_HELP)]
+    pub selector: ChunkSelector,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// The original PNG file
+    pub old_path: String,
+    /// The PNG file to compare it with
+    pub new_path: String,
+    /// How to lay out the differences
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct AnalyzeArgs {
+    /// PNG files, glob patterns or directories
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<String>,
+    /// Write an image of the least significant bits here
+    #[arg(long = "lsb-plane", value_name = "FILE")]
+    pub lsb_plane_file: Option<String>,
+    /// Descend into directories, processing every .png file
+    #[arg(short, long)]
+    pub recursive: bool,
+}
+
+
+#[derive(Args)]
+pub struct ExtractArgs {
+    /// PNG file
+    pub path: String,
+    /// Chunk index, chunk type or selector picking the chunks to extract
+    #[arg(long_help = SELECTOR_HELP)]
+    pub selector: ChunkSelector,
+    /// Directory to write the files to, named after each chunk's index and type
+    #[arg(short, long = "output", value_name = "DIR", value_parser = parse_directory)]
+    pub output_dir: String,
+    /// Write each whole chunk, with its length, type and CRC, not just its data
+    #[arg(short, long)]
+    pub whole: bool,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// PNG file
     pub path: String,
+    /// Four letter type of the new chunk
     pub chunk_type: String,
+    /// File holding the chunk's data
+    pub data_file: String,
+    /// Index to insert the chunk at; by default it goes just before IEND
+    #[arg(short, long, value_name = "N")]
+    pub index: Option<usize>,
+    /// Write the result here instead of back to the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<String>,
+    #[command(flatten)]
+    pub time: TimeArgs,
 }
-impl DecodeArgs {
-    pub fn new(args: &[String]) -> Result<Self> {
-        let args_length = args.len();
-        if args_length < 3 {
-            return Err(Box::new(ArgsError::NotEnoughArgs(args_length)));
-        } else if args_length > 3 {
-            return Err(Box::new(ArgsError::TooManyArgs(args_length)));
-        }
-        
-        Ok(Self {
-            path: args[1].clone(),
-            chunk_type: args[2].clone(),
-        })
-    }
+
+#[derive(Args)]
+pub struct ReplaceArgs {
+    /// PNG file
+    pub path: String,
+    /// Chunk index, chunk type or selector; the first match is replaced
+    #[arg(long_help = SELECTOR_HELP)]
+    pub selector: ChunkSelector,
+    /// New data for the chunk, as text
+    #[arg(short, long, required_unless_present = "data_file", conflicts_with = "data_file")]
+    pub message: Option<String>,
+    /// File holding the new data for the chunk
+    #[arg(short, long, value_name = "FILE")]
+    pub data_file: Option<String>,
+    /// Replace every selected chunk rather than the first
+    #[arg(short, long)]
+    pub all: bool,
+    /// Write the result here instead of back to the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<String>,
+    #[command(flatten)]
+    pub time: TimeArgs,
 }
 
-pub struct RemoveArgs {
+#[derive(Args)]
+pub struct RenameArgs {
+    /// PNG file
+    pub path: String,
+    /// Chunk index, chunk type or selector; the first match is renamed
+    #[arg(long_help = SELECTOR_HELP)]
+    pub selector: ChunkSelector,
+    /// The chunk's new four letter type
+    pub new_type: String,
+    /// Rename every selected chunk rather than the first
+    #[arg(short, long)]
+    pub all: bool,
+    /// Write the result here instead of back to the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<String>,
+    #[command(flatten)]
+    pub time: TimeArgs,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("destination").required(true).args(["before", "after", "to"])))]
+pub struct MoveArgs {
+    /// PNG file
     pub path: String,
+    /// Chunk index, chunk type or selector; the first match is moved
+    #[arg(long_help = SELECTOR_HELP)]
+    pub selector: ChunkSelector,
+    /// Put the chunk just before the first chunk this selects
+    #[arg(long, value_name = "SELECTOR")]
+    pub before: Option<ChunkSelector>,
+    /// Put the chunk just after the first chunk this selects
+    #[arg(long, value_name = "SELECTOR")]
+    pub after: Option<ChunkSelector>,
+    /// Put the chunk at this index
+    #[arg(long, value_name = "N")]
+    pub to: Option<usize>,
+    /// Write the result here instead of back to the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<String>,
+    #[command(flatten)]
+    pub time: TimeArgs,
+}
+
+#[derive(Args)]
+pub struct TransplantArgs {
+    /// PNG file to copy chunks from
+    pub source: String,
+    /// PNG file to copy chunks into
+    pub destination: String,
+    /// Only copy the ancillary chunks this selects
+    #[arg(short = 't', long, value_name = "SELECTOR", long_help = SELECTOR_HELP)]
+    pub chunk_type: Option<ChunkSelector>,
+    /// Copy unsafe-to-copy chunks even when the critical chunks differ
+    #[arg(long = "unsafe")]
+    pub allow_unsafe: bool,
+    /// Overwrite chunks the destination can only hold one of, such as gAMA
+    #[arg(long)]
+    pub replace: bool,
+    /// Write the result here instead of back to the destination file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<String>,
+    #[command(flatten)]
+    pub time: TimeArgs,
+}
+
+#[derive(Args)]
+pub struct StripArgs {
+    /// PNG files, glob patterns or directories
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<String>,
+    /// Which chunks to remove. Transparency and animation chunks are kept by
+    /// every preset.
+    #[arg(short, long, value_enum, default_value_t = Preset::Privacy)]
+    pub preset: Preset,
+    /// Keep chunks this selects, whatever the preset says; may be repeated
+    #[arg(short, long, value_name = "SELECTOR")]
+    pub keep: Vec<ChunkSelector>,
+    /// Also remove ancillary chunks this selects; may be repeated
+    #[arg(long, value_name = "SELECTOR")]
+    pub remove: Vec<ChunkSelector>,
+    /// Write the result here instead of back to the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<String>,
+    /// Descend into directories, processing every .png file
+    #[arg(short, long)]
+    pub recursive: bool,
+    #[command(flatten)]
+    pub time: TimeArgs,
+}
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    /// PNG files, glob patterns or directories
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<String>,
+    /// Descend into directories, processing every .png file
+    #[arg(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Args)]
+pub struct ExplainArgs {
+    /// Four letter chunk type, e.g. ruSt
     pub chunk_type: String,
 }
-impl RemoveArgs {
-    pub fn new(args: &[String]) -> Result<Self> {
-        let args_length = args.len();
-        if args_length < 3 {
-            return Err(Box::new(ArgsError::NotEnoughArgs(args_length)));
-        } else if args_length > 3 {
-            return Err(Box::new(ArgsError::TooManyArgs(args_length)));
-        }
-        
-        Ok(Self {
-            path: args[1].clone(),
-            chunk_type: args[2].clone(),
-        })
-    }
+
+#[derive(Args)]
+pub struct PaletteArgs {
+    /// PNG file
+    pub path: String,
+    /// Set a palette entry's colour, e.g. 3=#ff8000; may be repeated
+    #[arg(long = "set", value_name = "INDEX=#RRGGBB", value_parser = parse_colour_edit)]
+    pub colours: Vec<(usize, [u8; 3])>,
+    /// Set a palette entry's alpha, e.g. 3=128; may be repeated
+    #[arg(long = "alpha", value_name = "INDEX=ALPHA", value_parser = parse_alpha_edit)]
+    pub alphas: Vec<(usize, u8)>,
+    /// Write the result here instead of back to the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<String>,
+    #[command(flatten)]
+    pub time: TimeArgs,
 }
 
-pub struct PrintArgs {
+#[derive(Args)]
+pub struct ColourArgs {
+    /// PNG file
+    pub path: String,
+    /// Write the inflated iCCP profile to this file, e.g. profile.icc
+    #[arg(long, value_name = "FILE")]
+    pub extract_icc: Option<String>,
+    /// Set gAMA to this encoding gamma, e.g. 0.45455
+    #[arg(long, value_parser = parse_gamma)]
+    pub gamma: Option<u32>,
+    /// Set sRGB with this rendering intent
+    #[arg(long, value_name = "INTENT")]
+    pub srgb: Option<RenderingIntent>,
+    /// Set iCCP to the ICC profile in this file
+    #[arg(long, value_name = "FILE")]
+    pub import_icc: Option<String>,
+    /// Write the result here instead of back to the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<String>,
+    #[command(flatten)]
+    pub time: TimeArgs,
+}
+
+#[derive(Args)]
+pub struct ExifArgs {
+    /// PNG file
+    pub path: String,
+    /// Remove a group of tags; may be repeated
+    #[arg(long, value_name = "GROUP")]
+    pub remove: Vec<TagGroup>,
+    /// Remove a tag by name or number, e.g. Model or 0x0110; may be repeated
+    #[arg(long, value_name = "TAG", value_parser = parse_exif_tag)]
+    pub remove_tag: Vec<u16>,
+    /// Write the result here instead of back to the input file
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: Option<String>,
+    #[command(flatten)]
+    pub time: TimeArgs,
+}
+
+#[derive(Subcommand)]
+pub enum FramesArgs {
+    /// Write each frame of an animated PNG to a PNG file of its own
+    Extract(FramesExtractArgs),
+    /// Build an animated PNG from PNG files, one frame each
+    Assemble(FramesAssembleArgs),
+}
+
+#[derive(Args)]
+pub struct FramesExtractArgs {
+    /// Animated PNG file
     pub path: String,
+    /// Directory to write the frames to, named frame-0.png, frame-1.png and so on
+    #[arg(short, long = "output", value_name = "DIR", value_parser = parse_directory)]
+    pub output_dir: String,
+    /// Draw each frame over the ones before it on a full-size canvas, following
+    /// their blend and dispose operations, rather than writing only its region
+    #[arg(short, long)]
+    pub composite: bool,
+}
+
+#[derive(Args)]
+pub struct FramesAssembleArgs {
+    /// PNG files in frame order; the first is also shown by viewers without
+    /// APNG support, and the others must match its format, be no larger and
+    /// not be animated. A smaller frame is drawn at the top-left corner over
+    /// the previous one, which stays visible around it
+    #[arg(required = true)]
+    pub paths: Vec<String>,
+    /// File to write the animation to
+    #[arg(short, long = "output", value_name = "FILE")]
+    pub output_file: String,
+    /// How long each frame is shown, in milliseconds
+    #[arg(short, long, value_name = "MS", default_value_t = 100)]
+    pub delay: u16,
+    /// How many times the animation plays; 0 loops forever
+    #[arg(short, long, value_name = "N", default_value_t = 0)]
+    pub loops: u32,
+}
+
+fn parse_colour_edit(value: &str) -> Result<(usize, [u8; 3]), String> {
+    let (index, colour) = value.split_once('=').ok_or("expected INDEX=#RRGGBB")?;
+    let index = index.parse().map_err(|_| format!("{} is not a palette index", index))?;
+    let hex = colour.strip_prefix('#').unwrap_or(colour);
+    let channel = |i: usize| hex.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok());
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok((index, [r, g, b])),
+        _ => Err(format!("{} is not a colour like #ff8000", colour)),
+    }
+}
+
+fn parse_alpha_edit(value: &str) -> Result<(usize, u8), String> {
+    let (index, alpha) = value.split_once('=').ok_or("expected INDEX=ALPHA")?;
+    let index = index.parse().map_err(|_| format!("{} is not a palette index", index))?;
+    let alpha = alpha.parse().map_err(|_| format!("{} is not an alpha value from 0 to 255", alpha))?;
+    Ok((index, alpha))
+}
+
+// gAMA stores the gamma times 100000 in 31 bits
+fn parse_gamma(value: &str) -> Result<u32, String> {
+    match value.parse::<f64>() {
+        Ok(gamma) if gamma > 0.0 && gamma * 100000.0 <= i32::MAX as f64 => Ok((gamma * 100000.0).round() as u32),
+        _ => Err(format!("{} is not a gamma above 0", value)),
+    }
+}
+
+fn parse_exif_tag(value: &str) -> Result<u16, String> {
+    exif::parse_tag(value).ok_or(format!("{} is not a known EXIF tag name or a number like 0x0110", value))
+}
+
+// Several files cannot go to standard output, so - is not taken for a directory
+fn parse_directory(value: &str) -> Result<String, String> {
+    match value {
+        "-" => Err(String::from("several files are written, so they need a directory rather than standard output")),
+        _ => Ok(value.to_string()),
+    }
 }
-impl PrintArgs {
-    pub fn new(args: &[String]) -> Result<Self> {
-        let args_length = args.len();
-        if args_length < 2 {
-            return Err(Box::new(ArgsError::NotEnoughArgs(args_length)));
-        } else if args_length > 2 {
-            return Err(Box::new(ArgsError::TooManyArgs(args_length)));
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("png_me").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_encode_args() {
+        let cli = parse(&["encode", "a.png", "b.png", "-t", "ruSt", "-m", "hello", "-o", "out.png"]).unwrap();
+        match cli.command {
+            PngMeArgs::Encode(args) => {
+                assert_eq!(args.paths, vec!["a.png", "b.png"]);
+                assert_eq!(args.chunk_type.as_deref(), Some("ruSt"));
+                assert_eq!(args.message, "hello");
+                assert_eq!(args.output_file.as_deref(), Some("out.png"));
+            },
+            _ => panic!("expected encode"),
+        }
+    }
+
+    #[test]
+    fn test_palette_mode() {
+        assert!(parse(&["encode", "a.png", "--palette", "-m", "hi"]).is_ok());
+        assert!(parse(&["encode", "a.png", "--palette", "-t", "ruSt", "-m", "hi"]).is_err());
+        assert!(parse(&["encode", "a.png", "--palette", "--auto-type", "-m", "hi"]).is_err());
+        assert!(parse(&["decode", "a.png", "--palette"]).is_ok());
+        assert!(parse(&["decode", "a.png", "--palette", "--key", "k"]).is_err());
+    }
+
+    #[test]
+    fn test_missing_chunk_type() {
+        assert!(parse(&["decode", "a.png"]).is_err());
+    }
+
+    #[test]
+    fn test_remove_selection() {
+        assert!(parse(&["remove", "a.png", "-t", "ruSt", "--all"]).is_ok());
+        assert!(parse(&["remove", "a.png", "--index", "3"]).is_ok());
+        assert!(parse(&["remove", "a.png"]).is_err());
+        assert!(parse(&["remove", "a.png", "--all"]).is_err());
+        assert!(parse(&["remove", "a.png", "-t", "ruSt", "--index", "3"]).is_err());
+    }
+
+    #[test]
+    fn test_selector_value() {
+        assert!(parse(&["decode", "a.png", "-t", "private,r???", "--all"]).is_ok());
+        assert!(parse(&["inspect", "a.png", "^[a-z]"]).is_ok());
+        assert!(parse(&["inspect", "a.png", "nonsense"]).is_err());
+    }
+
+    #[test]
+    fn test_extract_requires_output() {
+        assert!(parse(&["extract", "a.png", "iCCP", "-o", "out"]).is_ok());
+        assert!(parse(&["extract", "a.png", "iCCP"]).is_err());
+        assert!(parse(&["extract", "a.png", "iCCP", "-o", "-"]).is_err());
+    }
+
+    #[test]
+    fn test_move_destination() {
+        assert!(parse(&["move", "a.png", "tEXt", "--before", "IDAT"]).is_ok());
+        assert!(parse(&["move", "a.png", "tEXt", "--to", "1"]).is_ok());
+        assert!(parse(&["move", "a.png", "tEXt"]).is_err());
+        assert!(parse(&["move", "a.png", "tEXt", "--to", "1", "--after", "IHDR"]).is_err());
+    }
+
+    #[test]
+    fn test_strip_lists() {
+        let cli = parse(&["strip", "a.png", "-p", "keep-colour", "-k", "tEXt", "-k", "private", "--remove", "pHYs"]).unwrap();
+        match cli.command {
+            PngMeArgs::Strip(args) => {
+                assert_eq!(args.preset, Preset::KeepColour);
+                assert_eq!(args.keep.len(), 2);
+                assert_eq!(args.remove.len(), 1);
+            },
+            _ => panic!("expected strip"),
+        }
+    }
+
+    #[test]
+    fn test_auto_type() {
+        assert!(parse(&["encode", "a.png", "--auto-type", "--key", "k", "-m", "hi"]).is_ok());
+        assert!(parse(&["encode", "a.png", "--auto-type", "-t", "ruSt", "-m", "hi"]).is_err());
+        assert!(parse(&["encode", "a.png", "--key", "k", "-t", "ruSt", "-m", "hi"]).is_err());
+        assert!(parse(&["decode", "a.png", "--key", "k"]).is_ok());
+    }
+
+    #[test]
+    fn test_palette_edits() {
+        assert_eq!(parse_colour_edit("3=#ff8000"), Ok((3, [255, 128, 0])));
+        assert_eq!(parse_colour_edit("0=00ff00"), Ok((0, [0, 255, 0])));
+        assert!(parse_colour_edit("3=#ff80").is_err());
+        assert!(parse_colour_edit("x=#ff8000").is_err());
+        assert_eq!(parse_alpha_edit("2=128"), Ok((2, 128)));
+        assert!(parse_alpha_edit("2=300").is_err());
+    }
+
+    #[test]
+    fn test_colour_args() {
+        let cli = parse(&["colour", "a.png", "--gamma", "0.45455", "--srgb", "relative-colorimetric"]).unwrap();
+        match cli.command {
+            PngMeArgs::Colour(args) => {
+                assert_eq!(args.gamma, Some(45455));
+                assert_eq!(args.srgb, Some(RenderingIntent::RelativeColorimetric));
+            },
+            _ => panic!("expected colour"),
         }
-        
-        Ok(Self {
-            path: args[1].clone(),
-        })
+        assert!(parse(&["colour", "a.png", "--gamma", "0"]).is_err());
+        assert!(parse(&["colour", "a.png", "--srgb", "vivid"]).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_exif_args() {
+        let cli = parse(&["exif", "a.png", "--remove", "gps", "--remove", "serials", "--remove-tag", "Model", "--remove-tag", "0x9003"]).unwrap();
+        match cli.command {
+            PngMeArgs::Exif(args) => {
+                assert_eq!(args.remove, vec![TagGroup::Gps, TagGroup::Serials]);
+                assert_eq!(args.remove_tag, vec![0x0110, 0x9003]);
+            },
+            _ => panic!("expected exif"),
+        }
+        assert!(parse(&["exif", "a.png", "--remove-tag", "Colour"]).is_err());
+    }
+
+    #[test]
+    fn test_frames_args() {
+        let cli = parse(&["frames", "extract", "anim.png", "-o", "out", "--composite"]).unwrap();
+        match cli.command {
+            PngMeArgs::Frames(FramesArgs::Extract(args)) => {
+                assert_eq!(args.output_dir, "out");
+                assert!(args.composite);
+            },
+            _ => panic!("expected frames extract"),
+        }
+        assert!(parse(&["frames", "extract", "anim.png"]).is_err());
+        assert!(parse(&["frames", "extract", "anim.png", "-o", "-"]).is_err());
+
+        let cli = parse(&["frames", "assemble", "a.png", "b.png", "-o", "anim.png", "--delay", "40"]).unwrap();
+        match cli.command {
+            PngMeArgs::Frames(FramesArgs::Assemble(args)) => {
+                assert_eq!(args.paths, vec!["a.png", "b.png"]);
+                assert_eq!((args.delay, args.loops), (40, 0));
+            },
+            _ => panic!("expected frames assemble"),
+        }
+        assert!(parse(&["frames", "assemble", "-o", "anim.png"]).is_err());
+    }
+
+    #[test]
+    fn test_time_args() {
+        match parse(&["remove", "a.png", "-t", "tEXt", "--preserve-time"]).unwrap().command {
+            PngMeArgs::Remove(args) => assert!(args.time.preserve_time && !args.time.update_time),
+            _ => panic!("expected remove"),
+        }
+        assert!(parse(&["strip", "a.png", "--update-time"]).is_ok());
+        assert!(parse(&["encode", "a.png", "-t", "ruSt", "-m", "hi", "--update-time", "--preserve-time"]).is_err());
+        assert!(parse(&["print", "a.png", "--preserve-time"]).is_err());
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert!(parse(&["print", "a.png", "--format", "json"]).is_ok());
+        assert!(parse(&["print", "a.png", "--format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_suggests_mistyped_command() {
+        let error = parse(&["decod", "a.png"]).err().unwrap().to_string();
+        assert!(error.contains("decode"));
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// A file to process, or something named on the command line that will be
+/// skipped and why.
+pub enum Input {
+    File(String),
+    Skip(String, String),
+}
+
+pub enum Outcome {
+    Done(Option<String>),
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Default)]
+pub struct Summary {
+    pub succeeded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Processed {} files: {} succeeded, {} skipped, {} failed",
+            self.succeeded + self.skipped + self.failed,
+            self.succeeded,
+            self.skipped,
+            self.failed,
+        )
+    }
+}
+
+/// Turns paths, glob patterns and (with `recursive`) directories into the list
+/// of files to process, in a stable order.
+pub fn expand_paths(paths: &[String], recursive: bool) -> Vec<Input> {
+    let mut inputs = vec![];
+    for path in paths {
+        if !path.contains(['*', '?', '[']) || Path::new(path).exists() {
+            expand_path(path, recursive, &mut inputs);
+            continue;
+        }
+        match glob::glob(path) {
+            Ok(matches) => {
+                let before = inputs.len();
+                for entry in matches {
+                    match entry {
+                        Ok(p) => expand_path(&p.to_string_lossy(), recursive, &mut inputs),
+                        Err(e) => inputs.push(Input::Skip(e.path().to_string_lossy().into_owned(), e.error().to_string())),
+                    }
+                }
+                if inputs.len() == before {
+                    inputs.push(Input::Skip(path.clone(), String::from("no files match this pattern")));
+                }
+            },
+            Err(e) => inputs.push(Input::Skip(path.clone(), e.to_string())),
+        }
+    }
+    inputs
+}
+
+fn expand_path(path: &str, recursive: bool, inputs: &mut Vec<Input>) {
+    if !Path::new(path).is_dir() {
+        inputs.push(Input::File(path.to_string()));
+    } else if recursive {
+        walk_directory(Path::new(path), inputs);
+    } else {
+        inputs.push(Input::Skip(path.to_string(), String::from("is a directory (use --recursive)")));
+    }
+}
+
+// Collects every `.png` file below a directory
+fn walk_directory(directory: &Path, inputs: &mut Vec<Input>) {
+    let mut entries: Vec<_> = match fs::read_dir(directory) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(e) => {
+            inputs.push(Input::Skip(directory.to_string_lossy().into_owned(), e.to_string()));
+            return;
+        },
+    };
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            walk_directory(&entry, inputs);
+        } else if entry.extension().is_some_and(|e| e.eq_ignore_ascii_case("png")) {
+            inputs.push(Input::File(entry.to_string_lossy().into_owned()));
+        }
+    }
+}
+
+/// Runs `job` over every input on a pool of threads. Each file's output is
+/// printed as soon as it and every file before it have finished, so the output
+/// keeps the order of the inputs, and a failure only affects its own file.
+/// Skips and failures are reported on standard error.
+pub fn run<F, E>(inputs: &[Input], job: F) -> Summary
+where
+    F: Fn(&str) -> Result<Option<String>, E> + Sync,
+    E: Display,
+{
+    struct Progress {
+        printed: usize,
+        outcomes: Vec<Option<Outcome>>,
+        summary: Summary,
+    }
+
+    let next = AtomicUsize::new(0);
+    let progress = Mutex::new(Progress {
+        printed: 0,
+        outcomes: inputs.iter().map(|_| None).collect(),
+        summary: Summary::default(),
+    });
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(inputs.len());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let outcome = match inputs.get(index) {
+                    None => break,
+                    Some(Input::Skip(_, reason)) => Outcome::Skipped(reason.clone()),
+                    Some(Input::File(path)) => match job(path) {
+                        Ok(output) => Outcome::Done(output),
+                        Err(e) => Outcome::Failed(e.to_string()),
+                    },
+                };
+
+                let mut guard = progress.lock().unwrap();
+                let progress = &mut *guard;
+                progress.outcomes[index] = Some(outcome);
+                while let Some(outcome) = progress.outcomes.get_mut(progress.printed).and_then(Option::take) {
+                    let path = match &inputs[progress.printed] {
+                        Input::File(p) | Input::Skip(p, _) => p,
+                    };
+                    match outcome {
+                        Outcome::Done(output) => {
+                            if let Some(output) = output {
+                                println!("{}", output);
+                            }
+                            progress.summary.succeeded += 1;
+                        },
+                        Outcome::Skipped(reason) => {
+                            eprintln!("{}: skipped, {}", path, reason);
+                            progress.summary.skipped += 1;
+                        },
+                        Outcome::Failed(error) => {
+                            eprintln!("{}: {}", path, error);
+                            progress.summary.failed += 1;
+                        },
+                    }
+                    progress.printed += 1;
+                }
+            });
+        }
+    });
+
+    progress.into_inner().unwrap().summary
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("png_me_batch_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("nested")).unwrap();
+        for file in ["a.png", "b.PNG", "notes.txt", "nested/c.png"] {
+            fs::write(root.join(file), b"").unwrap();
+        }
+        root
+    }
+
+    fn files(inputs: &[Input]) -> Vec<String> {
+        inputs
+            .iter()
+            .filter_map(|i| match i {
+                Input::File(p) => Some(Path::new(p).file_name().unwrap().to_string_lossy().into_owned()),
+                Input::Skip(..) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_recursive() {
+        let root = temp_tree("recursive");
+        let inputs = expand_paths(&[root.to_string_lossy().into_owned()], true);
+        assert_eq!(files(&inputs), vec!["a.png", "b.PNG", "c.png"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_expand_directory_without_recursive() {
+        let root = temp_tree("flat");
+        let inputs = expand_paths(&[root.to_string_lossy().into_owned()], false);
+        assert!(matches!(inputs[..], [Input::Skip(..)]));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let root = temp_tree("glob");
+        let pattern = root.join("*.png").to_string_lossy().into_owned();
+        assert_eq!(files(&expand_paths(&[pattern], false)), vec!["a.png"]);
+
+        let nothing = root.join("*.gif").to_string_lossy().into_owned();
+        assert!(matches!(expand_paths(&[nothing], false)[..], [Input::Skip(..)]));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_run_counts_outcomes() {
+        let inputs: Vec<Input> = (0..20)
+            .map(|i| if i == 3 { Input::Skip(i.to_string(), String::from("skip")) } else { Input::File(i.to_string()) })
+            .collect();
+        let summary = run(&inputs, |path| {
+            if path == "7" || path == "11" {
+                Err("bad file")
+            } else {
+                Ok(None)
+            }
+        });
+        assert_eq!(summary.succeeded, 17);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.failed, 2);
+    }
+}
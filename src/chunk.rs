@@ -1,10 +1,11 @@
-use std::{io::Read, error::Error, fmt::{Display, Formatter}};
+use std::fmt::{Display, Formatter};
 
 use crate::chunk_type::ChunkType;
 use crc::{Crc, CRC_32_ISO_HDLC};
 pub const CRC_CHECKER: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
 
+#[derive(Clone)]
 pub struct Chunk {
     pub chunk_type: ChunkType,
     pub chunk_data: Vec<u8>,
@@ -1,6 +1,6 @@
 use std::{str::FromStr, fmt::{Display, Formatter}};
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
@@ -32,6 +32,13 @@ impl ChunkType {
     pub fn is_safe_to_copy(&self) -> bool {
         self.bytes[3].is_ascii_lowercase()
     }
+
+    /// A valid private, ancillary, safe-to-copy type with letters taken from
+    /// `seed`, such as a hash of a key shared with whoever decodes the message.
+    pub fn private_from_seed(seed: u32) -> ChunkType {
+        let letter = |shift: u32| b'a' + ((seed >> shift) % 26) as u8;
+        ChunkType { bytes: [letter(0), letter(8), letter(16).to_ascii_uppercase(), letter(24)] }
+    }
 }
 
 
@@ -129,6 +136,18 @@ mod tests {
         assert!(!chunk.is_reserved_bit_valid());
     }
 
+    #[test]
+    pub fn test_private_from_seed() {
+        for seed in [0, 1, 0xdead_beef, u32::MAX] {
+            let chunk = ChunkType::private_from_seed(seed);
+            assert!(chunk.is_valid());
+            assert!(!chunk.is_critical());
+            assert!(!chunk.is_public());
+            assert!(chunk.is_safe_to_copy());
+        }
+        assert_ne!(ChunkType::private_from_seed(1), ChunkType::private_from_seed(2));
+    }
+
     #[test]
     pub fn test_chunk_type_is_safe_to_copy() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -0,0 +1,466 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::{decompress_to_vec_zlib_with_limit, TINFLStatus};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::{Error, Result};
+
+// gAMA and cHRM values an sRGB image should carry, times 100000
+// ICC profiles are rarely more than a few hundred kilobytes; inflating no more
+// than this stops a small iCCP chunk from filling memory
+const MAX_PROFILE_LEN: usize = 16 * 1024 * 1024;
+const SRGB_GAMMA: u32 = 45455;
+const SRGB_CHROMATICITIES: Chromaticities = Chromaticities {
+    white: [31270, 32900],
+    red: [64000, 33000],
+    green: [30000, 60000],
+    blue: [15000, 6000],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl Display for RenderingIntent {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let text = match self {
+            RenderingIntent::Perceptual => "perceptual",
+            RenderingIntent::RelativeColorimetric => "relative colorimetric",
+            RenderingIntent::Saturation => "saturation",
+            RenderingIntent::AbsoluteColorimetric => "absolute colorimetric",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// CIE x and y of the white point and primaries, times 100000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chromaticities {
+    pub white: [u32; 2],
+    pub red: [u32; 2],
+    pub green: [u32; 2],
+    pub blue: [u32; 2],
+}
+
+/// An ICC profile and its name, stored inflated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfile {
+    pub name: String,
+    pub profile: Vec<u8>,
+}
+
+/// ITU-T H.273 code points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodingPoints {
+    pub colour_primaries: u8,
+    pub transfer_function: u8,
+    pub matrix_coefficients: u8,
+    pub full_range: bool,
+}
+
+/// Chromaticities in units of 0.00002 and luminance in units of 0.0001 cd/m².
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasteringDisplay {
+    pub red: [u16; 2],
+    pub green: [u16; 2],
+    pub blue: [u16; 2],
+    pub white: [u16; 2],
+    pub max_luminance: u32,
+    pub min_luminance: u32,
+}
+
+/// Light levels in units of 0.0001 cd/m².
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LightLevel {
+    pub max_content: u32,
+    pub max_frame_average: u32,
+}
+
+/// A chunk describing how the image's samples map to colours.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColourChunk {
+    /// Gamma times 100000
+    Gamma(u32),
+    Chromaticities(Chromaticities),
+    Srgb(RenderingIntent),
+    Icc(IccProfile),
+    CodingPoints(CodingPoints),
+    MasteringDisplay(MasteringDisplay),
+    LightLevel(LightLevel),
+}
+
+impl ColourChunk {
+    pub const CHUNK_TYPES: [&'static str; 7] = ["gAMA", "cHRM", "sRGB", "iCCP", "cICP", "mDCv", "cLLI"];
+
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            ColourChunk::Gamma(_) => "gAMA",
+            ColourChunk::Chromaticities(_) => "cHRM",
+            ColourChunk::Srgb(_) => "sRGB",
+            ColourChunk::Icc(_) => "iCCP",
+            ColourChunk::CodingPoints(_) => "cICP",
+            ColourChunk::MasteringDisplay(_) => "mDCv",
+            ColourChunk::LightLevel(_) => "cLLI",
+        }
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        let data = match self {
+            ColourChunk::Gamma(gamma) => gamma.to_be_bytes().to_vec(),
+            ColourChunk::Chromaticities(c) => [c.white, c.red, c.green, c.blue].concat().iter().flat_map(|v| v.to_be_bytes()).collect(),
+            ColourChunk::Srgb(intent) => vec![*intent as u8],
+            ColourChunk::Icc(icc) => {
+                let mut data: Vec<u8> = icc.name.chars().map(|c| c as u8).collect();
+                data.extend([0, 0]);
+                data.extend(compress_to_vec_zlib(&icc.profile, 6));
+                data
+            },
+            ColourChunk::CodingPoints(c) => vec![c.colour_primaries, c.transfer_function, c.matrix_coefficients, c.full_range as u8],
+            ColourChunk::MasteringDisplay(m) => {
+                let mut data: Vec<u8> = [m.red, m.green, m.blue, m.white].concat().iter().flat_map(|v| v.to_be_bytes()).collect();
+                data.extend(m.max_luminance.to_be_bytes());
+                data.extend(m.min_luminance.to_be_bytes());
+                data
+            },
+            ColourChunk::LightLevel(l) => [l.max_content, l.max_frame_average].iter().flat_map(|v| v.to_be_bytes()).collect(),
+        };
+        Chunk::new(ChunkType::from_str(self.chunk_type()).unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for ColourChunk {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let chunk_type = chunk.chunk_type().to_string();
+        let data = chunk.data();
+        let (name, expected) = match chunk_type.as_str() {
+            "gAMA" => ("gAMA", Some(4)),
+            "cHRM" => ("cHRM", Some(32)),
+            "sRGB" => ("sRGB", Some(1)),
+            "cICP" => ("cICP", Some(4)),
+            "mDCv" => ("mDCv", Some(24)),
+            "cLLI" => ("cLLI", Some(8)),
+            "iCCP" => ("iCCP", None),
+            _ => return Err(Box::new(ColourError::NotColour(chunk_type))),
+        };
+        if expected.is_some_and(|n| n != data.len()) {
+            return Err(Box::new(ColourError::Length(name, data.len())));
+        }
+        let u32_at = |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let u16_at = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        match name {
+            "gAMA" => Ok(ColourChunk::Gamma(u32_at(0))),
+            "cHRM" => Ok(ColourChunk::Chromaticities(Chromaticities {
+                white: [u32_at(0), u32_at(4)],
+                red: [u32_at(8), u32_at(12)],
+                green: [u32_at(16), u32_at(20)],
+                blue: [u32_at(24), u32_at(28)],
+            })),
+            "sRGB" => match RenderingIntent::value_variants().get(data[0] as usize) {
+                Some(intent) => Ok(ColourChunk::Srgb(*intent)),
+                None => Err(Box::new(ColourError::Value("sRGB", "rendering intent", data[0] as u32))),
+            },
+            "iCCP" => {
+                let end = match data.iter().position(|&b| b == 0) {
+                    Some(i) if (1..80).contains(&i) => i,
+                    _ => return Err(Box::new(ColourError::ProfileName)),
+                };
+                if data.get(end + 1) != Some(&0) {
+                    return Err(Box::new(ColourError::Value("iCCP", "compression method", data.get(end + 1).copied().unwrap_or(0) as u32)));
+                }
+                let profile = match decompress_to_vec_zlib_with_limit(&data[end + 2..], MAX_PROFILE_LEN) {
+                    Ok(p) => p,
+                    Err(e) if e.status == TINFLStatus::HasMoreOutput => return Err(Box::new(ColourError::ProfileTooLarge(MAX_PROFILE_LEN))),
+                    Err(_) => return Err(Box::new(ColourError::Compression)),
+                };
+                Ok(ColourChunk::Icc(IccProfile { name: data[..end].iter().map(|&b| b as char).collect(), profile }))
+            },
+            "cICP" => match data[3] {
+                0 | 1 => Ok(ColourChunk::CodingPoints(CodingPoints {
+                    colour_primaries: data[0],
+                    transfer_function: data[1],
+                    matrix_coefficients: data[2],
+                    full_range: data[3] == 1,
+                })),
+                flag => Err(Box::new(ColourError::Value("cICP", "full range flag", flag as u32))),
+            },
+            "mDCv" => Ok(ColourChunk::MasteringDisplay(MasteringDisplay {
+                red: [u16_at(0), u16_at(2)],
+                green: [u16_at(4), u16_at(6)],
+                blue: [u16_at(8), u16_at(10)],
+                white: [u16_at(12), u16_at(14)],
+                max_luminance: u32_at(16),
+                min_luminance: u32_at(20),
+            })),
+            _ => Ok(ColourChunk::LightLevel(LightLevel { max_content: u32_at(0), max_frame_average: u32_at(4) })),
+        }
+    }
+}
+
+impl Display for ColourChunk {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let point = |[x, y]: [u32; 2]| format!("({:.4}, {:.4})", x as f64 / 100000.0, y as f64 / 100000.0);
+        let display_point = |[x, y]: [u16; 2]| format!("({:.4}, {:.4})", x as f64 * 0.00002, y as f64 * 0.00002);
+        let luminance = |l: u32| format!("{:.4} cd/m²", l as f64 / 10000.0);
+        write!(f, "{}  ", self.chunk_type())?;
+        match self {
+            ColourChunk::Gamma(0) => write!(f, "Gamma 0"),
+            ColourChunk::Gamma(gamma) => write!(f, "Gamma {:.5} (display exponent {:.2})", *gamma as f64 / 100000.0, 100000.0 / *gamma as f64),
+            ColourChunk::Chromaticities(c) => {
+                write!(f, "White {}, red {}, green {}, blue {}", point(c.white), point(c.red), point(c.green), point(c.blue))
+            },
+            ColourChunk::Srgb(intent) => write!(f, "sRGB colour space, {} rendering intent", intent),
+            ColourChunk::Icc(icc) => write!(f, "ICC profile \"{}\", {} bytes", icc.name, icc.profile.len()),
+            ColourChunk::CodingPoints(c) => write!(
+                f,
+                "Primaries {} ({}), transfer {} ({}), matrix {}, {} range",
+                c.colour_primaries,
+                primaries_name(c.colour_primaries),
+                c.transfer_function,
+                transfer_name(c.transfer_function),
+                c.matrix_coefficients,
+                if c.full_range { "full" } else { "narrow" },
+            ),
+            ColourChunk::MasteringDisplay(m) => write!(
+                f,
+                "Red {}, green {}, blue {}, white {}, luminance {} to {}",
+                display_point(m.red),
+                display_point(m.green),
+                display_point(m.blue),
+                display_point(m.white),
+                luminance(m.min_luminance),
+                luminance(m.max_luminance),
+            ),
+            ColourChunk::LightLevel(l) => write!(f, "MaxCLL {}, MaxFALL {}", luminance(l.max_content), luminance(l.max_frame_average)),
+        }
+    }
+}
+
+// Common H.273 colour primaries
+fn primaries_name(code: u8) -> &'static str {
+    match code {
+        1 => "BT.709",
+        9 => "BT.2020",
+        11 => "DCI-P3",
+        12 => "Display P3",
+        _ => "other",
+    }
+}
+
+// Common H.273 transfer characteristics
+fn transfer_name(code: u8) -> &'static str {
+    match code {
+        1 | 6 | 14 | 15 => "BT.709",
+        8 => "linear",
+        13 => "sRGB",
+        16 => "PQ",
+        18 => "HLG",
+        _ => "other",
+    }
+}
+
+/// The colour chunks of a file, in file order, with a problem for each one
+/// that does not parse.
+pub fn colour_chunks(png: &Png) -> (Vec<ColourChunk>, Vec<String>) {
+    let (mut chunks, mut problems) = (vec![], vec![]);
+    for chunk in png.chunks() {
+        if !ColourChunk::CHUNK_TYPES.contains(&chunk.chunk_type().to_string().as_str()) {
+            continue;
+        }
+        match ColourChunk::try_from(chunk) {
+            Ok(c) => chunks.push(c),
+            Err(e) => problems.push(e.to_string()),
+        }
+    }
+    (chunks, problems)
+}
+
+/// Problems with the colour chunks of a file: chunks that do not parse, values
+/// out of range and combinations that contradict each other.
+pub fn colour_problems(png: &Png) -> Vec<String> {
+    let (chunks, mut problems) = colour_chunks(png);
+    let find = |chunk_type: &str| chunks.iter().find(|c| c.chunk_type() == chunk_type);
+
+    for chunk in chunks.iter() {
+        match chunk {
+            ColourChunk::Gamma(0) => problems.push(String::from("gAMA must not be zero.")),
+            ColourChunk::CodingPoints(c) if c.matrix_coefficients != 0 => {
+                problems.push(format!("cICP matrix coefficients must be 0 for RGB images, not {}.", c.matrix_coefficients))
+            },
+            ColourChunk::MasteringDisplay(m) if m.min_luminance >= m.max_luminance => {
+                problems.push(String::from("mDCv minimum luminance is not below its maximum."))
+            },
+            ColourChunk::LightLevel(l) if l.max_frame_average > l.max_content => {
+                problems.push(String::from("cLLI MaxFALL is above MaxCLL."))
+            },
+            _ => {},
+        }
+    }
+
+    if find("sRGB").is_some() {
+        if find("iCCP").is_some() {
+            problems.push(String::from("sRGB and iCCP are both present; decoders will use the ICC profile."));
+        }
+        if let Some(ColourChunk::Gamma(gamma)) = find("gAMA") {
+            if *gamma != SRGB_GAMMA {
+                problems.push(format!("gAMA is {} but sRGB implies {}.", gamma, SRGB_GAMMA));
+            }
+        }
+        if let Some(ColourChunk::Chromaticities(c)) = find("cHRM") {
+            if *c != SRGB_CHROMATICITIES {
+                problems.push(String::from("cHRM does not match the sRGB primaries."));
+            }
+        }
+    }
+    problems
+}
+
+
+
+#[derive(Debug)]
+pub enum ColourError {
+    NotColour(String),
+    Length(&'static str, usize),
+    Value(&'static str, &'static str, u32),
+    ProfileName,
+    Compression,
+    ProfileTooLarge(usize),
+}
+impl Display for ColourError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ColourError::NotColour(t) => write!(f, "{} is not a colour chunk.", t),
+            ColourError::Length(chunk_type, n) => write!(f, "{} bytes is the wrong length for a {} chunk.", n, chunk_type),
+            ColourError::Value(chunk_type, field, value) => write!(f, "{} is not a valid {} {}.", value, chunk_type, field),
+            ColourError::ProfileName => write!(f, "The iCCP profile name must be 1 to 79 bytes followed by a null."),
+            ColourError::Compression => write!(f, "The iCCP profile could not be decompressed."),
+            ColourError::ProfileTooLarge(limit) => write!(f, "The iCCP profile inflates to more than {} bytes.", limit),
+        }
+    }
+}
+impl std::error::Error for ColourError {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_chunks() -> Vec<ColourChunk> {
+        vec![
+            ColourChunk::Gamma(SRGB_GAMMA),
+            ColourChunk::Chromaticities(SRGB_CHROMATICITIES),
+            ColourChunk::Srgb(RenderingIntent::RelativeColorimetric),
+            ColourChunk::Icc(IccProfile { name: String::from("Display"), profile: vec![7; 300] }),
+            ColourChunk::CodingPoints(CodingPoints { colour_primaries: 9, transfer_function: 16, matrix_coefficients: 0, full_range: true }),
+            ColourChunk::MasteringDisplay(MasteringDisplay {
+                red: [35400, 14600],
+                green: [8500, 39850],
+                blue: [6550, 2300],
+                white: [15635, 16450],
+                max_luminance: 10_000_000,
+                min_luminance: 50,
+            }),
+            ColourChunk::LightLevel(LightLevel { max_content: 10_000_000, max_frame_average: 4_000_000 }),
+        ]
+    }
+
+    fn png_with(chunks: &[Chunk]) -> Png {
+        let mut all = vec![chunk("IHDR", &[0; 13])];
+        all.extend_from_slice(chunks);
+        all.push(chunk("IEND", &[]));
+        Png::from_chunks(all)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for colour in testing_chunks() {
+            let written = colour.as_chunk();
+            assert_eq!(written.chunk_type().to_string(), colour.chunk_type());
+            assert_eq!(ColourChunk::try_from(&written).unwrap(), colour);
+        }
+    }
+
+    #[test]
+    fn test_parse_known_bytes() {
+        assert_eq!(ColourChunk::try_from(&chunk("gAMA", &[0, 0, 177, 143])).unwrap(), ColourChunk::Gamma(45455));
+        assert_eq!(ColourChunk::try_from(&chunk("sRGB", &[0])).unwrap(), ColourChunk::Srgb(RenderingIntent::Perceptual));
+        assert_eq!(ColourChunk::Srgb(RenderingIntent::AbsoluteColorimetric).as_chunk().data(), &[3]);
+    }
+
+    #[test]
+    fn test_invalid_chunks() {
+        assert!(ColourChunk::try_from(&chunk("gAMA", &[0, 1])).is_err());
+        assert!(ColourChunk::try_from(&chunk("sRGB", &[4])).is_err());
+        assert!(ColourChunk::try_from(&chunk("cICP", &[1, 13, 0, 2])).is_err());
+        assert!(ColourChunk::try_from(&chunk("iCCP", b"\0\0")).is_err());
+        assert!(ColourChunk::try_from(&chunk("iCCP", b"name\0\x01")).is_err());
+        assert!(ColourChunk::try_from(&chunk("iCCP", b"name\0\0not zlib")).is_err());
+        assert!(ColourChunk::try_from(&chunk("tEXt", &[])).is_err());
+    }
+
+    #[test]
+    fn test_oversized_profile() {
+        let mut data = b"name\0\0".to_vec();
+        data.extend(compress_to_vec_zlib(&vec![0; MAX_PROFILE_LEN + 1], 1));
+        let png = Png::from_chunks(vec![chunk("iCCP", &data)]);
+        assert_eq!(colour_problems(&png), [ColourError::ProfileTooLarge(MAX_PROFILE_LEN).to_string()]);
+    }
+
+    #[test]
+    fn test_display() {
+        let lines: Vec<String> = testing_chunks().iter().map(|c| c.to_string()).collect();
+        assert_eq!(lines[0], "gAMA  Gamma 0.45455 (display exponent 2.20)");
+        assert_eq!(lines[3], "iCCP  ICC profile \"Display\", 300 bytes");
+        assert_eq!(lines[4], "cICP  Primaries 9 (BT.2020), transfer 16 (PQ), matrix 0, full range");
+        assert!(lines[6].starts_with("cLLI  MaxCLL 1000.0000 cd/m²"));
+    }
+
+    #[test]
+    fn test_consistent_chunks() {
+        let chunks: Vec<Chunk> = testing_chunks().iter().filter(|c| c.chunk_type() != "iCCP").map(|c| c.as_chunk()).collect();
+        let png = png_with(&chunks);
+        assert_eq!(colour_chunks(&png).0.len(), 6);
+        assert!(colour_problems(&png).is_empty());
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let srgb = ColourChunk::Srgb(RenderingIntent::Perceptual).as_chunk();
+        let icc = testing_chunks()[3].as_chunk();
+        assert_eq!(colour_problems(&png_with(&[srgb.clone(), icc])).len(), 1);
+
+        let gamma = ColourChunk::Gamma(100000).as_chunk();
+        let mut chromaticities = SRGB_CHROMATICITIES;
+        chromaticities.white = [31000, 33000];
+        let chromaticities = ColourChunk::Chromaticities(chromaticities).as_chunk();
+        assert_eq!(colour_problems(&png_with(&[srgb.clone(), gamma.clone(), chromaticities])).len(), 2);
+        assert!(colour_problems(&png_with(&[gamma])).is_empty());
+    }
+
+    #[test]
+    fn test_out_of_range_values() {
+        let points = CodingPoints { colour_primaries: 1, transfer_function: 13, matrix_coefficients: 1, full_range: true };
+        let light = LightLevel { max_content: 10, max_frame_average: 20 };
+        let chunks = [
+            ColourChunk::Gamma(0).as_chunk(),
+            ColourChunk::CodingPoints(points).as_chunk(),
+            ColourChunk::LightLevel(light).as_chunk(),
+            chunk("sRGB", &[9]),
+        ];
+        assert_eq!(colour_problems(&png_with(&chunks)).len(), 4);
+    }
+}
@@ -1,161 +1,1063 @@
-use std::{fmt::{Display, Formatter}, error::Error, path::{Path, PathBuf}, io::{BufReader, Read, Write}, fs::{File, OpenOptions}, str::FromStr, process::Command};
+use std::{fmt::{Display, Formatter}, error::Error, path::{Path, PathBuf}, io::{BufReader, IsTerminal, Read, Write}, fs::{File, OpenOptions}, str::FromStr, collections::hash_map::RandomState, hash::{BuildHasher, Hasher}};
 
-use crate::{args::{PngMeArgs, EncodeArgs, DecodeArgs, RemoveArgs, PrintArgs}, png::Png, chunk::Chunk, chunk_type::ChunkType};
+use crate::{apng, batch::{self, Input}, args::{PngMeArgs, EncodeArgs, DecodeArgs, RemoveArgs, PrintArgs, InspectArgs, DiffArgs, AnalyzeArgs, ExtractArgs, ImportArgs, ReplaceArgs, RenameArgs, MoveArgs, TransplantArgs, StripArgs, ExplainArgs, PaletteArgs, ColourArgs, ExifArgs, FramesArgs, FramesAssembleArgs, FramesExtractArgs, TimeArgs}, png::{self, Png}, chunk::{Chunk, CRC_CHECKER}, chunk_type::ChunkType, colour::{self, ColourChunk, IccProfile}, image::{ColourType, Image, ImageHeader}, report::{self, FileSummary, PaletteTable}, palette::{self, Palette, PaletteError, Transparency}, palette_stego, diff::PngDiff, exif::Exif, explain::Explanation, inspect::ChunkInspection, metadata::ModificationTime, steganalysis::{self, Analysis}, select::ChunkSelector, transplant::{self, TransplantOptions}, strip, registry};
 
 
 #[derive(Debug)]
 pub enum CommandError {
-    SyntaxError,
-    FileError,
-    PngFormatError,
-    ChunkTypeError,
-    ChunkNotFoundError,
-    OtherError,
+    File,
+    PngFormat,
+    InvalidChunkType,
+    ChunkNotFound,
+    ImageData,
+    Output,
+    MessageNotFound,
+    CriticalChunkType(String),
+    RegisteredChunkType(String, &'static str),
+    Problems(Vec<String>),
+    InvalidIndex(usize),
+    Structure(String),
+    SingleFileOption(String),
+    Batch(usize),
+    NothingProcessed,
 }
 impl Display for CommandError {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         match self {
-            CommandError::SyntaxError => write!(f, "Syntax Error"),
-            CommandError::FileError => write!(f, "Error opening file"),
-            CommandError::PngFormatError => write!(f, "The input file is not a valid PNG file"),
-            CommandError::ChunkTypeError => write!(f, "Invalid chunk format"),
-            CommandError::ChunkNotFoundError => write!(f, "Chunk not found"),
-            CommandError::OtherError => write!(f, "Error: other"),
+            CommandError::File => write!(f, "Error opening file"),
+            CommandError::PngFormat => write!(f, "The input file is not a valid PNG file"),
+            CommandError::InvalidChunkType => write!(f, "Invalid chunk format"),
+            CommandError::ChunkNotFound => write!(f, "Chunk not found"),
+            CommandError::ImageData => write!(f, "The image data could not be decoded"),
+            CommandError::Output => write!(f, "The output could not be formatted"),
+            CommandError::MessageNotFound => write!(f, "No message found"),
+            CommandError::CriticalChunkType(t) => write!(f, "{} is a critical chunk type, which decoders must understand; use --force to write it anyway", t),
+            CommandError::RegisteredChunkType(t, name) => write!(f, "{} is a registered chunk type ({}), which other tools read; use --force to write it anyway", t, name),
+            CommandError::Problems(problems) => {
+                write!(f, "{} problem(s) found", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            },
+            CommandError::InvalidIndex(i) => write!(f, "There is no position {} in the file", i),
+            CommandError::Structure(e) => write!(f, "{}", e),
+            CommandError::SingleFileOption(o) => write!(f, "{} can only be used with a single input file", o),
+            CommandError::Batch(n) => write!(f, "{} file(s) could not be processed", n),
+            CommandError::NothingProcessed => write!(f, "No files were processed"),
         }
     }
 }
 impl std::error::Error for CommandError {}
 
+// Process exit codes, documented in `png_me --help`. Usage errors exit with 2,
+// which is what clap uses for the errors it reports itself.
+pub const EXIT_FAILURE: u8 = 1;
+pub const EXIT_USAGE: u8 = 2;
+pub const EXIT_IO: u8 = 3;
+pub const EXIT_INVALID_PNG: u8 = 4;
+pub const EXIT_NOT_FOUND: u8 = 5;
+pub const EXIT_BATCH: u8 = 6;
 
-pub fn process_command(command_args: PngMeArgs) -> Result<(), Box<dyn Error>> {
+impl CommandError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CommandError::File => EXIT_IO,
+            CommandError::PngFormat | CommandError::ImageData | CommandError::Problems(_) => EXIT_INVALID_PNG,
+            CommandError::InvalidChunkType
+            | CommandError::CriticalChunkType(_)
+            | CommandError::RegisteredChunkType(..)
+            | CommandError::InvalidIndex(_)
+            | CommandError::SingleFileOption(_) => EXIT_USAGE,
+            CommandError::ChunkNotFound | CommandError::MessageNotFound => EXIT_NOT_FOUND,
+            CommandError::Batch(_) | CommandError::NothingProcessed => EXIT_BATCH,
+            CommandError::Output | CommandError::Structure(_) => EXIT_FAILURE,
+        }
+    }
+}
+
+
+pub fn process_command(command_args: PngMeArgs) -> Result<(), CommandError> {
     match command_args {
         PngMeArgs::Encode(args) => {
-            return match encode(args) {
-                Ok(_) => Ok(()),
-                Err(e) => Err(Box::new(e)),
+            let inputs = batch::expand_paths(&args.paths, args.recursive);
+            if args.output_file.is_some() && inputs.len() > 1 {
+                return Err(CommandError::SingleFileOption(String::from("--output")));
+            }
+            if args.palette {
+                return run_batch(&inputs, |path, _| encode_palette(path, &args).map(|_| None));
             }
+            let chunk_type = match &args.chunk_type {
+                Some(t) => match ChunkType::from_str(t) {
+                    Ok(c) => c,
+                    Err(_) => return Err(CommandError::InvalidChunkType),
+                },
+                None => auto_chunk_type(args.key.as_deref()),
+            };
+            check_message_type(&chunk_type, args.force)?;
+            if args.auto_type {
+                eprintln!("Chunk type: {}", chunk_type);
+            }
+            run_batch(&inputs, |path, _| encode(path, &chunk_type, &args).map(|_| None))
         },
         PngMeArgs::Decode(args) => {
-            match decode(args) {
-                Ok(message_chunk) => {
-                    match message_chunk {
-                        Some(m) => println!("Hidden message: {}", m),
-                        None => println!("No message found"),
+            let inputs = batch::expand_paths(&args.paths, args.recursive);
+            let piped = !std::io::stdout().is_terminal();
+            run_batch(&inputs, |path, labelled| {
+                if args.palette {
+                    let message = decode_palette(path)?;
+                    if piped && !labelled {
+                        return match write_to_file(STDIO_PATH, &message) {
+                            Ok(_) => Ok(None),
+                            Err(_) => Err(CommandError::File),
+                        };
                     }
-                    return Ok(());
-                },
-                Err(e) => return Err(Box::new(e)),
-            }
+                    let output = format!("Hidden message in palette: {}", String::from_utf8_lossy(&message));
+                    return Ok(Some(if labelled { format!("{}: {}", path, output) } else { output }));
+                }
+                let messages = decode(path, &args)?;
+                if messages.is_empty() {
+                    return Err(CommandError::MessageNotFound);
+                }
+                // When piped, a single file's message is written out exactly as stored
+                if piped && !labelled && !args.all {
+                    return match write_to_file(STDIO_PATH, messages[0].1.data()) {
+                        Ok(_) => Ok(None),
+                        Err(_) => Err(CommandError::File),
+                    };
+                }
+                let lines: Vec<String> = messages
+                    .iter()
+                    .map(|(index, chunk)| {
+                        let text = chunk.data_as_string().unwrap_or_else(|_| String::from_utf8_lossy(chunk.data()).into_owned());
+                        let output = match args.all {
+                            true => format!("Hidden message in chunk {} ({}): {}", index, chunk.chunk_type(), text),
+                            false => format!("Hidden message: {}", text),
+                        };
+                        if labelled { format!("{}: {}", path, output) } else { output }
+                    })
+                    .collect();
+                Ok(Some(lines.join("\n")))
+            })
         },
         PngMeArgs::Remove(args) => {
-            return match remove(args) {
-                Ok(_) => Ok(()),
-                Err(e) => Err(Box::new(e)),
-            }
+            let inputs = batch::expand_paths(&args.paths, args.recursive);
+            run_batch(&inputs, |path, _| remove(path, &args).map(|_| None))
         },
         PngMeArgs::Print(args) => {
-            return match print(args) {
-                Ok(_) => Ok(()),
-                Err(e) => Err(Box::new(e)),
+            let inputs = batch::expand_paths(&args.paths, args.recursive);
+            run_batch(&inputs, |path, labelled| print(path, &args, labelled).map(Some))
+        },
+        PngMeArgs::Inspect(args) => inspect(args),
+        PngMeArgs::Diff(args) => diff(args),
+        PngMeArgs::Extract(args) => extract(args),
+        PngMeArgs::Import(args) => import(args),
+        PngMeArgs::Replace(args) => replace(args),
+        PngMeArgs::Rename(args) => rename(args),
+        PngMeArgs::Move(args) => move_chunk(args),
+        PngMeArgs::Transplant(args) => transplant(args),
+        PngMeArgs::Explain(args) => explain(args),
+        PngMeArgs::Palette(args) => edit_palette(args),
+        PngMeArgs::Colour(args) => edit_colour(args),
+        PngMeArgs::Exif(args) => edit_exif(args),
+        PngMeArgs::Frames(FramesArgs::Extract(args)) => extract_frames(args),
+        PngMeArgs::Frames(FramesArgs::Assemble(args)) => assemble_frames(args),
+        PngMeArgs::Validate(args) => {
+            let inputs = batch::expand_paths(&args.paths, args.recursive);
+            run_batch(&inputs, |path, labelled| {
+                validate(path)?;
+                Ok(Some(if labelled { format!("{}: valid", path) } else { String::from("valid") }))
+            })
+        },
+        PngMeArgs::Strip(args) => {
+            let inputs = batch::expand_paths(&args.paths, args.recursive);
+            if args.output_file.is_some() && inputs.len() > 1 {
+                return Err(CommandError::SingleFileOption(String::from("--output")));
+            }
+            run_batch(&inputs, |path, labelled| {
+                let removed = strip(path, &args)?;
+                let mut lines: Vec<String> = removed.iter().map(|(index, chunk)| format!("Removed {} from index {}", chunk.chunk_type(), index)).collect();
+                if lines.is_empty() {
+                    lines.push(String::from("Nothing to remove"));
+                }
+                if labelled {
+                    lines = lines.iter().map(|line| format!("{}: {}", path, line)).collect();
+                }
+                if args.output_file.as_deref().unwrap_or(path) == STDIO_PATH {
+                    print_report(&lines.join("\n"), STDIO_PATH);
+                    return Ok(None);
+                }
+                Ok(Some(lines.join("\n")))
+            })
+        },
+        PngMeArgs::Analyze(args) => {
+            let inputs = batch::expand_paths(&args.paths, args.recursive);
+            if args.lsb_plane_file.is_some() && inputs.len() > 1 {
+                return Err(CommandError::SingleFileOption(String::from("--lsb-plane")));
             }
+            run_batch(&inputs, |path, labelled| {
+                let analysis = analyze(path, &args)?;
+                Ok(Some(if labelled { format!("{}\n{}", path, analysis) } else { analysis.to_string() }))
+            })
+        }
+    }
+}
+
+// Runs a command over every input. A single file behaves as it always has, with
+// its error returned; several files are processed in parallel, reporting each
+// failure as it happens and a summary at the end. The job is told whether its
+// output needs labelling with the file it came from. Inputs that were all
+// skipped are an error too, so that scripts notice nothing was done.
+fn run_batch<F>(inputs: &[Input], job: F) -> Result<(), CommandError>
+where
+    F: Fn(&str, bool) -> Result<Option<String>, CommandError> + Sync,
+{
+    if let [Input::File(path)] = inputs {
+        if let Some(output) = job(path, false)? {
+            println!("{}", output);
+        }
+        return Ok(());
+    }
+
+    let summary = batch::run(inputs, |path| job(path, true));
+    eprintln!("{}", summary);
+    match (summary.failed, summary.succeeded) {
+        (0, 0) => Err(CommandError::NothingProcessed),
+        (0, _) => Ok(()),
+        (failed, _) => Err(CommandError::Batch(failed)),
+    }
+}
+
+// A private type for a message, derived from a key or else picked at random
+fn auto_chunk_type(key: Option<&str>) -> ChunkType {
+    let seed = match key {
+        Some(k) => CRC_CHECKER.checksum(k.as_bytes()),
+        None => RandomState::new().build_hasher().finish() as u32,
+    };
+    ChunkType::private_from_seed(seed)
+}
+
+// Message chunks must be ignorable by decoders: valid and ancillary, unless forced.
+// Registered types, private ones like fdAT included, would be read as what they
+// are registered for, and sequenced APNG chunks have their data renumbered, so
+// they are refused too. Other public types are left to the PNG registry, so
+// using one gets a warning.
+fn check_message_type(chunk_type: &ChunkType, force: bool) -> Result<(), CommandError> {
+    if !force && !chunk_type.is_valid() {
+        return Err(CommandError::InvalidChunkType);
+    }
+    if !force && chunk_type.is_critical() {
+        return Err(CommandError::CriticalChunkType(chunk_type.to_string()));
+    }
+    if let Some(info) = registry::lookup(&chunk_type.to_string()) {
+        if !force {
+            return Err(CommandError::RegisteredChunkType(chunk_type.to_string(), info.name));
+        }
+    }
+    if chunk_type.is_public() {
+        eprintln!("Warning: {} is a public chunk type, reserved for chunks registered with the PNG specification", chunk_type);
+    }
+    Ok(())
+}
+
+fn encode(path: &str, chunk_type: &ChunkType, args: &EncodeArgs) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let mut png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let new_chunk = Chunk::new(chunk_type.clone(), args.message.clone().into_bytes());
+    png.append_chunk(new_chunk);
+    let destination_path = match &args.output_file {
+        Some(p) => p,
+        None => path,
+    };
+    write_edited_png(&mut png, destination_path, &args.time)
+}
+
+fn encode_palette(path: &str, args: &EncodeArgs) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let mut png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    if let Err(e) = palette_stego::embed(&mut png, args.message.as_bytes()) {
+        return Err(CommandError::Structure(e.to_string()));
+    }
+    write_edited_png(&mut png, args.output_file.as_deref().unwrap_or(path), &args.time)
+}
+
+fn decode_palette(path: &str) -> Result<Vec<u8>, CommandError> {
+    let opened_file =  match load_bytes_from_file(path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    match palette_stego::extract(&png) {
+        Ok(m) => Ok(m),
+        Err(_) => Err(CommandError::MessageNotFound),
+    }
+}
+
+// The first selected chunk, or every one with `--all`, paired with its index
+fn decode(path: &str, args: &DecodeArgs) -> Result<Vec<(usize, Chunk)>, CommandError> {
+    let opened_file =  match load_bytes_from_file(path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let selector = match (&args.chunk_type, &args.key) {
+        (Some(selector), _) => selector.clone(),
+        (None, key) => match ChunkSelector::from_str(&auto_chunk_type(key.as_deref()).to_string()) {
+            Ok(s) => s,
+            Err(_) => return Err(CommandError::InvalidChunkType),
+        },
+    };
+    let matches = png.select(&selector).map(|(i, c)| (i, c.clone()));
+    match args.all {
+        true => Ok(matches.collect()),
+        false => Ok(matches.take(1).collect()),
+    }
+}
+
+fn remove(path: &str, args: &RemoveArgs) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let mut png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+
+    // Without --all only the first selected chunk goes
+    let first_selected = match &args.chunk_type {
+        Some(selector) if !args.all => png.select(selector).next().map(|(index, _)| index),
+        _ => None,
+    };
+    let removed = match (&args.chunk_type, args.index.or(first_selected)) {
+        (_, Some(index)) if index < png.chunks().len() => png.remove_chunk_at(index).map(|_| ()),
+        (Some(selector), None) if args.all && png.select(selector).next().is_some() => png.remove_selected(selector).map(|_| ()),
+        _ => return Err(CommandError::ChunkNotFound),
+    };
+    if let Err(e) = removed {
+        return Err(CommandError::Structure(e.to_string()));
+    }
+
+    write_edited_png(&mut png, path, &args.time)
+}
+
+fn print(path: &str, args: &PrintArgs, labelled: bool) -> Result<String, CommandError> {
+    let opened_file =  match load_bytes_from_file(path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let raw_chunks = match Png::scan(&opened_file) {
+        Ok(r) => r,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+
+    let mut summary = FileSummary::new(path, &raw_chunks);
+    if args.palette {
+        let png = Png::from_chunks(raw_chunks.into_iter().map(|raw| raw.chunk).collect());
+        summary.palette = match read_palette(&png)? {
+            Some((p, t)) => Some(palette::palette_entries(&p, t.as_ref())),
+            None => Some(vec![]),
+        };
+    }
+    let rendered = match labelled {
+        true => report::render_in_batch(&summary, args.format),
+        false => report::render(&summary, args.format),
+    };
+    match rendered {
+        Ok(output) => Ok(output),
+        Err(_) => Err(CommandError::Output),
+    }
+}
+
+// The palette and its transparency, if the image has a palette
+fn read_palette(png: &Png) -> Result<Option<(Palette, Option<Transparency>)>, CommandError> {
+    let header = match ImageHeader::from_png(png) {
+        Ok(h) => h,
+        Err(_) => return Err(CommandError::ImageData),
+    };
+    let palette = match Palette::from_png(png) {
+        Ok(Some(p)) => p,
+        Ok(None) => return Ok(None),
+        Err(_) => return Err(CommandError::ImageData),
+    };
+    match Transparency::from_png(png, &header) {
+        Ok(t) => Ok(Some((palette, t))),
+        Err(_) => Err(CommandError::ImageData),
+    }
+}
+
+fn inspect(args: InspectArgs) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(&args.path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let raw_chunks = match Png::scan(&opened_file) {
+        Ok(r) => r,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+
+    let inspections: Vec<ChunkInspection> = raw_chunks
+        .iter()
+        .enumerate()
+        .filter(|(index, raw)| args.selector.matches(*index, raw.chunk.chunk_type()))
+        .map(|(index, raw)| ChunkInspection { index, raw })
+        .collect();
+    if inspections.is_empty() {
+        return Err(CommandError::ChunkNotFound);
+    }
+
+    let output: Vec<String> = inspections.iter().map(|i| i.to_string()).collect();
+    println!("{}", output.join("\n"));
+    Ok(())
+}
+
+fn diff(args: DiffArgs) -> Result<(), CommandError> {
+    let mut pngs = vec![];
+    for path in [&args.old_path, &args.new_path] {
+        let opened_file =  match load_bytes_from_file(path) {
+            Ok(b) => b,
+            Err(_) => return Err(CommandError::File),
+        };
+        match Png::try_from(&opened_file[..]) {
+            Ok(p) => pngs.push(p),
+            Err(_) => return Err(CommandError::PngFormat),
+        };
+    }
+
+    let png_diff = PngDiff::new(&args.old_path, &pngs[0], &args.new_path, &pngs[1]);
+    match report::render(&png_diff, args.format) {
+        Ok(output) => println!("{}", output),
+        Err(_) => return Err(CommandError::Output),
+    }
+    Ok(())
+}
+
+fn analyze(path: &str, args: &AnalyzeArgs) -> Result<Analysis, CommandError> {
+    let opened_file =  match load_bytes_from_file(path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let image = match Image::decode(&png) {
+        Ok(i) => i,
+        Err(_) => return Err(CommandError::ImageData),
+    };
+
+    if let Some(lsb_plane_file) = &args.lsb_plane_file {
+        let plane = match steganalysis::lsb_plane(&image) {
+            Ok(p) => p,
+            Err(_) => return Err(CommandError::ImageData),
+        };
+        if write_to_file(lsb_plane_file, &plane.to_png().as_bytes()).is_err() {
+            return Err(CommandError::File);
         }
+    }
+    Ok(Analysis::of_image(&image))
+}
+
+fn extract(args: ExtractArgs) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(&args.path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
     };
+    let selected: Vec<(usize, &Chunk)> = png.select(&args.selector).collect();
+    if selected.is_empty() {
+        return Err(CommandError::ChunkNotFound);
+    }
+
+    if std::fs::create_dir_all(&args.output_dir).is_err() {
+        return Err(CommandError::File);
+    }
+    for (index, chunk) in selected {
+        let (bytes, extension) = match args.whole {
+            true => (chunk.as_bytes(), "chunk"),
+            false => (chunk.data().to_vec(), "bin"),
+        };
+        let file_path = Path::new(&args.output_dir).join(format!("{}-{}.{}", index, chunk.chunk_type(), extension));
+        if write_to_file(&file_path, &bytes).is_err() {
+            return Err(CommandError::File);
+        }
+        println!("{}", file_path.display());
+    }
     Ok(())
 }
 
-fn encode(args: EncodeArgs) -> Result<(), CommandError> {
+fn extract_frames(args: FramesExtractArgs) -> Result<(), CommandError> {
     let opened_file =  match load_bytes_from_file(&args.path) {
         Ok(b) => b,
-        Err(_) => return Err(CommandError::FileError),
+        Err(_) => return Err(CommandError::File),
+    };
+    let png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let frames = match apng::frames(&png) {
+        Ok(f) if f.is_empty() => return Err(CommandError::ChunkNotFound),
+        Ok(f) => f,
+        Err(e) => return Err(CommandError::Structure(e.to_string())),
+    };
+    let pngs = match args.composite {
+        true => match apng::composite_frames(&png) {
+            Ok(p) => p,
+            Err(e) => return Err(CommandError::Structure(e.to_string())),
+        },
+        false => match frames.iter().map(|frame| apng::frame_png(&png, frame)).collect::<Result<Vec<Png>, _>>() {
+            Ok(p) => p,
+            Err(e) => return Err(CommandError::Structure(e.to_string())),
+        },
+    };
+
+    if std::fs::create_dir_all(&args.output_dir).is_err() {
+        return Err(CommandError::File);
+    }
+    // Pad the numbers so that the files sort in frame order
+    let digits = (pngs.len() - 1).to_string().len();
+    for (number, frame) in pngs.iter().enumerate() {
+        let file_path = Path::new(&args.output_dir).join(format!("frame-{:0digits$}.png", number));
+        if write_to_file(&file_path, &frame.as_bytes()).is_err() {
+            return Err(CommandError::File);
+        }
+        println!("{}", file_path.display());
+    }
+    Ok(())
+}
+
+fn assemble_frames(args: FramesAssembleArgs) -> Result<(), CommandError> {
+    let mut images = vec![];
+    for path in &args.paths {
+        let opened_file =  match load_bytes_from_file(path) {
+            Ok(b) => b,
+            Err(_) => return Err(CommandError::File),
+        };
+        match Png::try_from(&opened_file[..]) {
+            Ok(p) => images.push(p),
+            Err(_) => return Err(CommandError::PngFormat),
+        };
+    }
+    let animation = match apng::assemble(&images, (args.delay, 1000), args.loops) {
+        Ok(a) => a,
+        Err(e) => return Err(CommandError::Structure(e.to_string())),
+    };
+    write_png(&animation, &args.output_file)
+}
+
+fn import(args: ImportArgs) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(&args.path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
     };
     let mut png = match Png::try_from(&opened_file[..]) {
         Ok(p) => p,
-        Err(_) => return Err(CommandError::PngFormatError),
+        Err(_) => return Err(CommandError::PngFormat),
     };
-    let new_chunktype = match ChunkType::from_str(&args.chunk_type) {
+    let chunk_type = match ChunkType::from_str(&args.chunk_type) {
         Ok(c) => c,
-        Err(_) => return Err(CommandError::ChunkTypeError),
+        Err(_) => return Err(CommandError::InvalidChunkType),
     };
-    let new_chunk = Chunk::new(new_chunktype, args.message.into_bytes());
-    png.append_chunk(new_chunk);
+    let data = match load_bytes_from_file(&args.data_file) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+
+    let index = args.index.unwrap_or(before_end(&png));
+    if index > png.chunks().len() {
+        return Err(CommandError::InvalidIndex(index));
+    }
+    if let Err(e) = png.insert_chunk(index, Chunk::new(chunk_type, data)) {
+        return Err(CommandError::Structure(e.to_string()));
+    }
+
     let destination_path = match &args.output_file {
         Some(p) => p,
         None => &args.path,
     };
-    match write_to_file(destination_path, &png.as_bytes()) {
-        Ok(_) => Ok(()),
-        Err(_) => Err(CommandError::FileError),
+    write_edited_png(&mut png, destination_path, &args.time)
+}
+
+// Where a chunk goes to be appended: just before IEND
+fn before_end(png: &Png) -> usize {
+    match png.chunks().last() {
+        Some(last) if last.chunk_type().to_string() == "IEND" => png.chunks().len() - 1,
+        _ => png.chunks().len(),
+    }
+}
+
+// The indices of the chunks an edit applies to: the first selected, or all of them
+fn selected_indices(png: &Png, selector: &ChunkSelector, all: bool) -> Result<Vec<usize>, CommandError> {
+    let indices: Vec<usize> = png.select(selector).map(|(index, _)| index).take(if all { usize::MAX } else { 1 }).collect();
+    match indices.is_empty() {
+        true => Err(CommandError::ChunkNotFound),
+        false => Ok(indices),
     }
 }
 
-fn decode(args: DecodeArgs) -> Result<Option<String>, CommandError> {
+fn replace(args: ReplaceArgs) -> Result<(), CommandError> {
     let opened_file =  match load_bytes_from_file(&args.path) {
         Ok(b) => b,
-        Err(_) => return Err(CommandError::FileError),
+        Err(_) => return Err(CommandError::File),
     };
-    let png = match Png::try_from(&opened_file[..]) {
+    let mut png = match Png::try_from(&opened_file[..]) {
         Ok(p) => p,
-        Err(_) => return Err(CommandError::PngFormatError),
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let data = match (&args.message, &args.data_file) {
+        (Some(message), _) => message.clone().into_bytes(),
+        (None, Some(data_file)) => match load_bytes_from_file(data_file) {
+            Ok(b) => b,
+            Err(_) => return Err(CommandError::File),
+        },
+        (None, None) => vec![],
     };
-    match png.chunk_by_type(&args.chunk_type) {
-        Some(c) => Ok(Some(c.data_as_string().unwrap())),
-        None => Ok(None),
+
+    for index in selected_indices(&png, &args.selector, args.all)? {
+        if let Err(e) = png.replace_chunk_data(index, data.clone()) {
+            return Err(CommandError::Structure(e.to_string()));
+        }
     }
+    write_edited_png(&mut png, args.output_file.as_deref().unwrap_or(&args.path), &args.time)
 }
 
-fn remove(args: RemoveArgs) -> Result<(), CommandError> {
+fn rename(args: RenameArgs) -> Result<(), CommandError> {
     let opened_file =  match load_bytes_from_file(&args.path) {
         Ok(b) => b,
-        Err(_) => return Err(CommandError::FileError),
+        Err(_) => return Err(CommandError::File),
     };
     let mut png = match Png::try_from(&opened_file[..]) {
         Ok(p) => p,
-        Err(_) => return Err(CommandError::PngFormatError),
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let new_type = match ChunkType::from_str(&args.new_type) {
+        Ok(c) if c.is_valid() => c,
+        _ => return Err(CommandError::InvalidChunkType),
     };
 
-    if let Err(_) =  png.remove_chunk(&args.chunk_type) {
-        return Err(CommandError::ChunkNotFoundError);
+    for index in selected_indices(&png, &args.selector, args.all)? {
+        if let Err(e) = png.rename_chunk(index, new_type.clone()) {
+            return Err(CommandError::Structure(e.to_string()));
+        }
     }
+    write_edited_png(&mut png, args.output_file.as_deref().unwrap_or(&args.path), &args.time)
+}
 
-    match write_to_file(&args.path, &png.as_bytes()) {
+fn move_chunk(args: MoveArgs) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(&args.path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let mut png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let from = selected_indices(&png, &args.selector, false)?[0];
+
+    // --before and --after name chunks as they are before this one is taken out
+    let after_removal = |destination: usize| if destination > from { destination - 1 } else { destination };
+    let to = match (&args.before, &args.after, args.to) {
+        (Some(before), _, _) => after_removal(selected_indices(&png, before, false)?[0]),
+        (_, Some(after), _) => after_removal(selected_indices(&png, after, false)?[0] + 1),
+        (_, _, Some(to)) if to < png.chunks().len() => to,
+        (_, _, Some(to)) => return Err(CommandError::InvalidIndex(to)),
+        _ => from,
+    };
+    if let Err(e) = png.move_chunk(from, to) {
+        return Err(CommandError::Structure(e.to_string()));
+    }
+    write_edited_png(&mut png, args.output_file.as_deref().unwrap_or(&args.path), &args.time)
+}
+
+fn transplant(args: TransplantArgs) -> Result<(), CommandError> {
+    let mut pngs = vec![];
+    for path in [&args.source, &args.destination] {
+        let opened_file =  match load_bytes_from_file(path) {
+            Ok(b) => b,
+            Err(_) => return Err(CommandError::File),
+        };
+        match Png::try_from(&opened_file[..]) {
+            Ok(p) => pngs.push(p),
+            Err(_) => return Err(CommandError::PngFormat),
+        };
+    }
+    let mut destination = pngs.remove(1);
+
+    let options = TransplantOptions { allow_unsafe: args.allow_unsafe, replace_existing: args.replace };
+    let report = match transplant::transplant(&pngs[0], &mut destination, args.chunk_type.as_ref(), &options) {
+        Ok(r) => r,
+        Err(e) => return Err(CommandError::Structure(e.to_string())),
+    };
+    let destination_path = args.output_file.as_deref().unwrap_or(&args.destination);
+    write_edited_png(&mut destination, destination_path, &args.time)?;
+    let lines: Vec<String> = report.iter().map(|t| t.to_string()).collect();
+    if !lines.is_empty() {
+        print_report(&lines.join("\n"), destination_path);
+    }
+    Ok(())
+}
+
+fn strip(path: &str, args: &StripArgs) -> Result<Vec<(usize, Chunk)>, CommandError> {
+    let opened_file =  match load_bytes_from_file(path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let mut png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let removed = match strip::strip(&mut png, args.preset, &args.keep, &args.remove) {
+        Ok(r) => r,
+        Err(e) => return Err(CommandError::Structure(e.to_string())),
+    };
+    // A strip that removes nothing leaves the file as it was, tIME included
+    let destination = args.output_file.as_deref().unwrap_or(path);
+    match (removed.is_empty(), destination == path) {
+        (true, true) => {},
+        (true, false) => write_png(&png, destination)?,
+        (false, _) => write_edited_png(&mut png, destination, &args.time)?,
+    }
+    Ok(removed)
+}
+
+fn validate(path: &str) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let raw_chunks = match Png::scan(&opened_file) {
+        Ok(r) => r,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let mut problems = png::validation_problems(&raw_chunks);
+    let png = Png::from_chunks(raw_chunks.into_iter().map(|raw| raw.chunk).collect());
+    if ImageHeader::from_png(&png).is_ok() {
+        problems.extend(palette::palette_problems(&png));
+        problems.extend(colour::colour_problems(&png));
+    }
+    problems.extend(apng::apng_problems(&png));
+    match problems {
+        problems if problems.is_empty() => Ok(()),
+        problems => Err(CommandError::Problems(problems)),
+    }
+}
+
+fn explain(args: ExplainArgs) -> Result<(), CommandError> {
+    let chunk_type = match ChunkType::from_str(&args.chunk_type) {
+        Ok(c) => c,
+        Err(_) => return Err(CommandError::InvalidChunkType),
+    };
+    println!("{}", Explanation { chunk_type });
+    Ok(())
+}
+
+fn edit_palette(args: PaletteArgs) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(&args.path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let mut png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let (mut palette, transparency) = match read_palette(&png)? {
+        Some(p) => p,
+        None => return Err(CommandError::ChunkNotFound),
+    };
+
+    if !args.colours.is_empty() || !args.alphas.is_empty() {
+        let header = match ImageHeader::from_png(&png) {
+            Ok(h) => h,
+            Err(_) => return Err(CommandError::ImageData),
+        };
+        if !args.alphas.is_empty() && header.colour_type != ColourType::Indexed {
+            return Err(CommandError::Structure(PaletteError::AlphaWithoutPalette.to_string()));
+        }
+        for (index, rgb) in args.colours.iter() {
+            if palette.set_entry(*index, *rgb).is_err() {
+                return Err(CommandError::InvalidIndex(*index));
+            }
+        }
+        // tRNS is only touched when the file has one or alphas were given
+        let mut transparency = match (transparency, args.alphas.is_empty()) {
+            (None, true) => None,
+            (t, _) => Some(t.unwrap_or(Transparency::Palette(vec![]))),
+        };
+        if let Some(transparency) = transparency.as_mut() {
+            for (index, alpha) in args.alphas.iter() {
+                if let Err(e) = transparency.set_alpha(*index, *alpha) {
+                    return Err(CommandError::Structure(e.to_string()));
+                }
+            }
+            if let Err(e) = transparency.validate(&header, Some(&palette)) {
+                return Err(CommandError::Structure(e.to_string()));
+            }
+        }
+        write_palette(&mut png, &palette, transparency.as_ref())?;
+        let destination = args.output_file.as_deref().unwrap_or(&args.path);
+        write_edited_png(&mut png, destination, &args.time)?;
+        print_report(&palette_report(&png, &palette, transparency.as_ref()), destination);
+        return Ok(());
+    }
+    println!("{}", palette_report(&png, &palette, transparency.as_ref()));
+    Ok(())
+}
+
+// The palette table, and the capacity for hiding a message in it. Only still
+// indexed images can be renumbered to follow a reordered palette.
+fn palette_report(png: &Png, palette: &Palette, transparency: Option<&Transparency>) -> String {
+    let table = PaletteTable(&palette::palette_entries(palette, transparency)).to_string();
+    match ImageHeader::from_png(png) {
+        Ok(ImageHeader { colour_type: ColourType::Indexed, .. }) if !apng::is_animated(png) => {
+            format!("{}\nCapacity for encode --palette: {} bytes", table, palette_stego::capacity(palette, transparency))
+        },
+        _ => table,
+    }
+}
+
+fn edit_colour(args: ColourArgs) -> Result<(), CommandError> {
+    let opened_file =  match load_bytes_from_file(&args.path) {
+        Ok(b) => b,
+        Err(_) => return Err(CommandError::File),
+    };
+    let mut png = match Png::try_from(&opened_file[..]) {
+        Ok(p) => p,
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+
+    let mut edits = vec![];
+    if let Some(gamma) = args.gamma {
+        edits.push(ColourChunk::Gamma(gamma));
+    }
+    if let Some(intent) = args.srgb {
+        edits.push(ColourChunk::Srgb(intent));
+    }
+    if let Some(file) = &args.import_icc {
+        let profile = match load_bytes_from_file(file) {
+            Ok(b) => b,
+            Err(_) => return Err(CommandError::File),
+        };
+        edits.push(ColourChunk::Icc(IccProfile { name: profile_name(file), profile }));
+    }
+    if !edits.is_empty() {
+        for edit in edits.iter() {
+            set_colour_chunk(&mut png, edit)?;
+        }
+        write_edited_png(&mut png, args.output_file.as_deref().unwrap_or(&args.path), &args.time)?;
+    }
+
+    let (chunks, _) = colour::colour_chunks(&png);
+    if let Some(file) = &args.extract_icc {
+        let icc = match chunks.iter().find_map(|c| if let ColourChunk::Icc(icc) = c { Some(icc) } else { None }) {
+            Some(i) => i,
+            None => return Err(CommandError::ChunkNotFound),
+        };
+        if write_to_file(file, &icc.profile).is_err() {
+            return Err(CommandError::File);
+        }
+    }
+    let mut lines: Vec<String> = chunks.iter().map(|c| c.to_string()).collect();
+    if lines.is_empty() {
+        lines.push(String::from("No colour chunks"));
+    }
+    lines.extend(colour::colour_problems(&png).iter().map(|p| format!("Problem: {}", p)));
+    let written_to_stdout = (!edits.is_empty() && args.output_file.as_deref().unwrap_or(&args.path) == STDIO_PATH)
+        || args.extract_icc.as_deref() == Some(STDIO_PATH);
+    print_report(&lines.join("\n"), if written_to_stdout { STDIO_PATH } else { &args.path });
+    Ok(())
+}
+
+// Replaces the chunk of the same type, or adds one straight after IHDR
+fn set_colour_chunk(png: &mut Png, colour: &ColourChunk) -> Result<(), CommandError> {
+    let chunk = colour.as_chunk();
+    let result = match png.chunks().iter().position(|c| c.chunk_type() == chunk.chunk_type()) {
+        Some(i) => png.replace_chunk_data(i, chunk.data().to_vec()).map(|_| ()),
+        None => png.insert_chunk(1, chunk),
+    };
+    match result {
         Ok(_) => Ok(()),
-        Err(_) => Err(CommandError::FileError),
+        Err(e) => Err(CommandError::Structure(e.to_string())),
     }
 }
 
-fn print(args: PrintArgs) -> Result<(), CommandError> {
+// iCCP names are 1 to 79 printable Latin-1 characters
+fn profile_name(path: &str) -> String {
+    let stem = Path::new(path).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name: String = stem.chars().filter(|&c| (' '..='~').contains(&c) || ('\u{a1}'..='\u{ff}').contains(&c)).take(79).collect();
+    match name.trim() {
+        "" => String::from("ICC profile"),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+fn edit_exif(args: ExifArgs) -> Result<(), CommandError> {
     let opened_file =  match load_bytes_from_file(&args.path) {
         Ok(b) => b,
-        Err(_) => return Err(CommandError::FileError),
+        Err(_) => return Err(CommandError::File),
     };
-    let png = match Png::try_from(&opened_file[..]) {
+    let mut png = match Png::try_from(&opened_file[..]) {
         Ok(p) => p,
-        Err(_) => return Err(CommandError::PngFormatError),
+        Err(_) => return Err(CommandError::PngFormat),
+    };
+    let index = match png.chunks().iter().position(|c| c.chunk_type().to_string() == Exif::CHUNK_TYPE) {
+        Some(i) => i,
+        None => return Err(CommandError::ChunkNotFound),
+    };
+    let mut exif = match Exif::try_from(&png.chunks()[index]) {
+        Ok(e) => e,
+        Err(e) => return Err(CommandError::Structure(e.to_string())),
     };
 
-    println!("{}", png);
+    if !args.remove.is_empty() || !args.remove_tag.is_empty() {
+        let destination = args.output_file.as_deref().unwrap_or(&args.path);
+        let removed = exif.remove(|directory, tag| args.remove.iter().any(|group| group.contains(directory, tag)) || args.remove_tag.contains(&tag));
+        if removed.is_empty() {
+            print_report("Nothing to remove", destination);
+            return Ok(());
+        }
+        if let Err(e) = png.replace_chunk_data(index, exif.as_chunk().data().to_vec()) {
+            return Err(CommandError::Structure(e.to_string()));
+        }
+        write_edited_png(&mut png, destination, &args.time)?;
+        let lines: Vec<String> = removed.iter().map(|r| format!("Removed {}", r)).collect();
+        print_report(&lines.join("\n"), destination);
+        return Ok(());
+    }
+    match exif.tags().is_empty() {
+        true => println!("No EXIF tags"),
+        false => println!("{}", exif),
+    }
     Ok(())
 }
 
+fn write_palette(png: &mut Png, palette: &Palette, transparency: Option<&Transparency>) -> Result<(), CommandError> {
+    match palette::store(png, palette, transparency) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(CommandError::Structure(e.to_string())),
+    }
+}
+
+// Writes an edited file, first setting an existing tIME, or with
+// --update-time any tIME, to the current time unless --preserve-time is given
+fn write_edited_png(png: &mut Png, path: &str, time: &TimeArgs) -> Result<(), CommandError> {
+    if !time.preserve_time {
+        let chunk = ModificationTime::now().as_chunk();
+        let result = match png.chunks().iter().position(|c| c.chunk_type() == chunk.chunk_type()) {
+            Some(i) => png.replace_chunk_data(i, chunk.data().to_vec()).map(|_| ()),
+            None if time.update_time => png.insert_chunk(before_end(png), chunk),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            return Err(CommandError::Structure(e.to_string()));
+        }
+    }
+    write_png(png, path)
+}
+
+// Reports on an edit go to stderr when the edited file is written to stdout
+fn print_report(report: &str, destination: &str) {
+    match destination == STDIO_PATH {
+        true => eprintln!("{}", report),
+        false => println!("{}", report),
+    }
+}
+
+fn write_png(png: &Png, path: &str) -> Result<(), CommandError> {
+    match write_to_file(path, &png.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(CommandError::File),
+    }
+}
+
+
 
+// Standard input when reading, standard output when writing
+const STDIO_PATH: &str = "-";
 
 fn load_bytes_from_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Box <dyn Error>>  {
-    let file = File::open(path)?;
     let mut buffer = vec![];
+    if path.as_ref() == Path::new(STDIO_PATH) {
+        std::io::stdin().lock().read_to_end(&mut buffer)?;
+        return Ok(buffer);
+    }
+    let file = File::open(path)?;
     let mut reader = BufReader::new(file);
-    reader.read_to_end(&mut buffer);
+    reader.read_to_end(&mut buffer)?;
     Ok(buffer)
 }
 
 fn write_to_file<P: AsRef<Path>>(path: P, bytes: &[u8]) -> Result<(), Box <dyn Error>>  {
+    if path.as_ref() == Path::new(STDIO_PATH) {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
     //Set up file path and temp file path 
     let write_path = Path::new(path.as_ref());
     let write_path = PathBuf::from(write_path);
-    let temp_path = write_path.clone().with_extension("tmp");
+    let mut temp_path = write_path.clone().into_os_string();
+    temp_path.push(".tmp");
 
     //Create temp file and write contents to it
-    let mut temp_file = OpenOptions::new().read(true).write(true).create(true).open(&temp_path)?;
+    let mut temp_file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&temp_path)?;
     temp_file.write_all(bytes)?;
     std::fs::rename(&temp_path, &write_path)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(chunk_type: &str, force: bool) -> Result<(), CommandError> {
+        check_message_type(&ChunkType::from_str(chunk_type).unwrap(), force)
+    }
+
+    #[test]
+    fn test_message_types() {
+        assert!(check("ruSt", false).is_ok());
+        assert!(matches!(check("RuSt", false), Err(CommandError::CriticalChunkType(_))));
+        assert!(check("RuSt", true).is_ok());
+    }
+
+    #[test]
+    fn test_registered_message_types() {
+        for chunk_type in ["fdAT", "fcTL", "acTL", "tEXt"] {
+            assert!(matches!(check(chunk_type, false), Err(CommandError::RegisteredChunkType(..))));
+        }
+        assert!(check("fdAT", true).is_ok());
+    }
+
+    #[test]
+    fn test_batch_with_nothing_processed() {
+        let inputs = [Input::Skip(String::from("images"), String::from("is a directory (use --recursive)"))];
+        let result = run_batch(&inputs, |_, _| Ok(None));
+        assert!(matches!(result, Err(CommandError::NothingProcessed)));
+        assert_eq!(CommandError::NothingProcessed.exit_code(), EXIT_BATCH);
+    }
+}
@@ -0,0 +1,355 @@
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::image::Image;
+use crate::inspect;
+use crate::png::Png;
+use crate::report::data_preview;
+
+/// One difference between the chunk lists of two files. Indices are positions
+/// in the old and new files respectively.
+#[derive(Serialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum ChunkChange {
+    Added {
+        index: usize,
+        #[serde(rename = "type")]
+        chunk_type: String,
+    },
+    Removed {
+        index: usize,
+        #[serde(rename = "type")]
+        chunk_type: String,
+    },
+    Moved {
+        from: usize,
+        to: usize,
+        #[serde(rename = "type")]
+        chunk_type: String,
+    },
+    Changed {
+        from: usize,
+        to: usize,
+        #[serde(rename = "type")]
+        chunk_type: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+impl ChunkChange {
+    // Where the change shows up, used to list changes in file order
+    fn position(&self) -> usize {
+        match self {
+            ChunkChange::Added { index, .. } | ChunkChange::Removed { index, .. } => *index,
+            ChunkChange::Moved { to, .. } | ChunkChange::Changed { to, .. } => *to,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct FieldChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PixelComparison {
+    Identical,
+    Different,
+    Undecodable,
+}
+
+#[derive(Serialize)]
+pub struct PngDiff {
+    pub old_path: String,
+    pub new_path: String,
+    pub changes: Vec<ChunkChange>,
+    pub pixels: PixelComparison,
+}
+
+impl PngDiff {
+    pub fn new(old_path: &str, old: &Png, new_path: &str, new: &Png) -> Self {
+        PngDiff {
+            old_path: old_path.to_string(),
+            new_path: new_path.to_string(),
+            changes: chunk_changes(old.chunks(), new.chunks()),
+            pixels: compare_pixels(old, new),
+        }
+    }
+}
+
+impl Display for PngDiff {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(f, "--- {}", self.old_path)?;
+        writeln!(f, "+++ {}", self.new_path)?;
+        if self.changes.is_empty() {
+            writeln!(f, "No chunk differences")?;
+        }
+        for change in self.changes.iter() {
+            match change {
+                ChunkChange::Added { index, chunk_type } => writeln!(f, "+ [{}] {} added", index, chunk_type)?,
+                ChunkChange::Removed { index, chunk_type } => writeln!(f, "- [{}] {} removed", index, chunk_type)?,
+                ChunkChange::Moved { from, to, chunk_type } => writeln!(f, "> [{} -> {}] {} moved", from, to, chunk_type)?,
+                ChunkChange::Changed { from, to, chunk_type, fields } => {
+                    writeln!(f, "~ [{} -> {}] {} changed", from, to, chunk_type)?;
+                    for field in fields {
+                        let old = field.old.as_deref().unwrap_or("(none)");
+                        let new = field.new.as_deref().unwrap_or("(none)");
+                        writeln!(f, "    {}: {} -> {}", field.name, old, new)?;
+                    }
+                },
+            }
+        }
+        write!(f, "Pixels: {}", match self.pixels {
+            PixelComparison::Identical => "identical",
+            PixelComparison::Different => "different",
+            PixelComparison::Undecodable => "could not be decoded",
+        })
+    }
+}
+
+fn same_chunk(a: &Chunk, b: &Chunk) -> bool {
+    a.chunk_type() == b.chunk_type() && a.data() == b.data()
+}
+
+/// Aligns two chunk lists. Chunks in the longest common subsequence are
+/// unchanged; identical chunks outside it have moved, chunks of the same type
+/// have changed, and whatever is left was added or removed.
+pub fn chunk_changes(old: &[Chunk], new: &[Chunk]) -> Vec<ChunkChange> {
+    let (n, m) = (old.len(), new.len());
+    // Files usually share most of their chunks, so only the middle is aligned
+    let prefix = old.iter().zip(new).take_while(|(a, b)| same_chunk(a, b)).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| same_chunk(a, b)).count();
+    let (old_middle, new_middle): (Vec<&Chunk>, Vec<&Chunk>) = (old[prefix..n - suffix].iter().collect(), new[prefix..m - suffix].iter().collect());
+    let (mut matched_old, mut matched_new) = (vec![false; n], vec![false; m]);
+    for i in (0..prefix).chain(n - suffix..n) {
+        matched_old[i] = true;
+    }
+    for j in (0..prefix).chain(m - suffix..m) {
+        matched_new[j] = true;
+    }
+    let mut pairs = vec![];
+    common_subsequence(&old_middle, &new_middle, (prefix, prefix), &mut pairs);
+    for (i, j) in pairs {
+        matched_old[i] = true;
+        matched_new[j] = true;
+    }
+    let mut unmatched_old: Vec<usize> = (0..n).filter(|&i| !matched_old[i]).collect();
+    let mut unmatched_new: Vec<usize> = (0..m).filter(|&j| !matched_new[j]).collect();
+
+    let mut changes = vec![];
+    let mut pair_up = |matches: &dyn Fn(&Chunk, &Chunk) -> bool, moved: bool, unmatched_old: &mut Vec<usize>, unmatched_new: &mut Vec<usize>| {
+        unmatched_old.retain(|&from| {
+            match unmatched_new.iter().position(|&to| matches(&old[from], &new[to])) {
+                Some(position) => {
+                    let to = unmatched_new.remove(position);
+                    let chunk_type = old[from].chunk_type().to_string();
+                    changes.push(if moved {
+                        ChunkChange::Moved { from, to, chunk_type }
+                    } else {
+                        ChunkChange::Changed { from, to, chunk_type, fields: field_changes(&old[from], &new[to]) }
+                    });
+                    false
+                },
+                None => true,
+            }
+        });
+    };
+    pair_up(&same_chunk, true, &mut unmatched_old, &mut unmatched_new);
+    pair_up(&|a: &Chunk, b: &Chunk| a.chunk_type() == b.chunk_type(), false, &mut unmatched_old, &mut unmatched_new);
+
+    changes.extend(unmatched_old.into_iter().map(|index| ChunkChange::Removed { index, chunk_type: old[index].chunk_type().to_string() }));
+    changes.extend(unmatched_new.into_iter().map(|index| ChunkChange::Added { index, chunk_type: new[index].chunk_type().to_string() }));
+    changes.sort_by_key(|change| change.position());
+    changes
+}
+
+// Hirschberg's algorithm: finds a longest common subsequence of the two lists
+// in space linear in their lengths, adding the matched index pairs, offset by
+// where the lists start in their files, to `pairs`
+fn common_subsequence(old: &[&Chunk], new: &[&Chunk], offset: (usize, usize), pairs: &mut Vec<(usize, usize)>) {
+    if old.is_empty() || new.is_empty() {
+        return;
+    }
+    if old.len() == 1 {
+        if let Some(j) = new.iter().position(|chunk| same_chunk(old[0], chunk)) {
+            pairs.push((offset.0, offset.1 + j));
+        }
+        return;
+    }
+    let middle = old.len() / 2;
+    let forward = subsequence_lengths(old[..middle].iter(), new.iter());
+    let backward = subsequence_lengths(old[middle..].iter().rev(), new.iter().rev());
+    let split = (0..=new.len()).max_by_key(|&j| (forward[j] + backward[new.len() - j], std::cmp::Reverse(j))).unwrap_or(0);
+    common_subsequence(&old[..middle], &new[..split], offset, pairs);
+    common_subsequence(&old[middle..], &new[split..], (offset.0 + middle, offset.1 + split), pairs);
+}
+
+// The length of the longest common subsequence of `old` and each prefix of `new`
+fn subsequence_lengths<'a, I, J>(old: I, new: J) -> Vec<usize>
+where
+    I: Iterator<Item = &'a &'a Chunk>,
+    J: Iterator<Item = &'a &'a Chunk> + Clone,
+{
+    let mut row = vec![0; new.clone().count() + 1];
+    for a in old {
+        let mut diagonal = 0;
+        for (j, b) in new.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if same_chunk(a, b) { diagonal + 1 } else { above.max(row[j]) };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+/// Compares the decoded fields of two chunks of the same type, falling back to
+/// the CRC and a preview of the raw data for types without a known layout, or
+/// when the data differs in a way none of the fields show.
+pub fn field_changes(old: &Chunk, new: &Chunk) -> Vec<FieldChange> {
+    let data_change = || {
+        vec![FieldChange {
+            name: String::from("Data"),
+            old: Some(format!("{} bytes, CRC {:08x}, {}", old.length(), old.crc(), data_preview(old.data()))),
+            new: Some(format!("{} bytes, CRC {:08x}, {}", new.length(), new.crc(), data_preview(new.data()))),
+        }]
+    };
+    let (old_fields, new_fields) = match (inspect::fields(old), inspect::fields(new)) {
+        (Ok(o), Ok(n)) if !o.is_empty() || !n.is_empty() => (o, n),
+        _ => return data_change(),
+    };
+
+    let lookup = |fields: &[(String, String)], name: &str| fields.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone());
+    let mut names: Vec<&String> = old_fields.iter().map(|(n, _)| n).collect();
+    names.extend(new_fields.iter().map(|(n, _)| n).filter(|n| lookup(&old_fields, n).is_none()));
+    let changes: Vec<FieldChange> = names
+        .into_iter()
+        .filter_map(|name| {
+            let (old_value, new_value) = (lookup(&old_fields, name), lookup(&new_fields, name));
+            if old_value == new_value {
+                return None;
+            }
+            Some(FieldChange { name: name.clone(), old: old_value, new: new_value })
+        })
+        .collect();
+    match changes.is_empty() && old.data() != new.data() {
+        true => data_change(),
+        false => changes,
+    }
+}
+
+/// Decodes both images and compares their dimensions, sample format and
+/// samples. Indexed images must also share the same palette.
+pub fn compare_pixels(old: &Png, new: &Png) -> PixelComparison {
+    let (old_image, new_image) = match (Image::decode(old), Image::decode(new)) {
+        (Ok(o), Ok(n)) => (o, n),
+        _ => return PixelComparison::Undecodable,
+    };
+    let (old_header, new_header) = (old_image.header(), new_image.header());
+    let same_format = old_header.width == new_header.width
+        && old_header.height == new_header.height
+        && old_header.bit_depth == new_header.bit_depth
+        && old_header.colour_type == new_header.colour_type;
+    let palette = |png: &Png| png.chunk_by_type("PLTE").map(|c| c.data().to_vec());
+    if same_format && old_image.samples() == new_image.samples() && palette(old) == palette(new) {
+        PixelComparison::Identical
+    } else {
+        PixelComparison::Different
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::image::ColourType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let chunks = vec![chunk("FrSt", "a"), chunk("miDl", "b")];
+        assert!(chunk_changes(&chunks, &chunks).is_empty());
+    }
+
+    #[test]
+    fn test_added_and_removed() {
+        let old = vec![chunk("FrSt", "a"), chunk("miDl", "b")];
+        let new = vec![chunk("FrSt", "a"), chunk("LASt", "c")];
+        let changes = chunk_changes(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(&changes[0], ChunkChange::Removed { index: 1, chunk_type } if chunk_type == "miDl"));
+        assert!(matches!(&changes[1], ChunkChange::Added { index: 1, chunk_type } if chunk_type == "LASt"));
+    }
+
+    #[test]
+    fn test_moved() {
+        let old = vec![chunk("FrSt", "a"), chunk("miDl", "b"), chunk("LASt", "c")];
+        let new = vec![chunk("miDl", "b"), chunk("LASt", "c"), chunk("FrSt", "a")];
+        let changes = chunk_changes(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(&changes[0], ChunkChange::Moved { from: 0, to: 2, .. }));
+    }
+
+    #[test]
+    fn test_many_chunks() {
+        let old: Vec<Chunk> = (0..1000).map(|i| chunk("IDAT", &i.to_string())).collect();
+        let mut new = old.clone();
+        new[300] = chunk("IDAT", "changed");
+        new.insert(600, chunk("tEXt", "a\0b"));
+        new.swap(10, 800);
+        let changes = chunk_changes(&old, &new);
+        assert_eq!(changes.len(), 4);
+        assert!(matches!(&changes[0], ChunkChange::Moved { from: 799, to: 10, .. }));
+        assert!(matches!(&changes[1], ChunkChange::Changed { from: 300, to: 300, .. }));
+        assert!(matches!(&changes[2], ChunkChange::Added { index: 600, .. }));
+        assert!(matches!(&changes[3], ChunkChange::Moved { from: 10, to: 800, .. }));
+    }
+
+    #[test]
+    fn test_changed_text_fields() {
+        let old = vec![chunk("tEXt", "Author\0Ferris")];
+        let new = vec![chunk("tEXt", "Author\0Corro")];
+        let changes = chunk_changes(&old, &new);
+        match &changes[0] {
+            ChunkChange::Changed { fields, .. } => {
+                assert_eq!(fields.len(), 1);
+                assert_eq!(fields[0].name, "Text");
+                assert_eq!(fields[0].old.as_deref(), Some("Ferris"));
+                assert_eq!(fields[0].new.as_deref(), Some("Corro"));
+            },
+            _ => panic!("expected a changed chunk"),
+        }
+    }
+
+    #[test]
+    fn test_changed_compressed_text() {
+        let old = chunk("zTXt", "Comment\0\0abcd");
+        let new = chunk("zTXt", "Comment\0\0abce");
+        let fields = field_changes(&old, &new);
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].name, "Data");
+        assert_ne!(fields[0].old, fields[0].new);
+    }
+
+    #[test]
+    fn test_compare_pixels() {
+        let image = Image::new(2, 2, 8, ColourType::Greyscale, vec![1, 2, 3, 4]).unwrap();
+        let mut png = image.to_png();
+        let other = Image::new(2, 2, 8, ColourType::Greyscale, vec![1, 2, 3, 5]).unwrap().to_png();
+        png.append_chunk(chunk("ruSt", "hidden"));
+
+        assert_eq!(compare_pixels(&image.to_png(), &png), PixelComparison::Identical);
+        assert_eq!(compare_pixels(&png, &other), PixelComparison::Different);
+        assert_eq!(compare_pixels(&Png::from_chunks(vec![]), &png), PixelComparison::Undecodable);
+    }
+}
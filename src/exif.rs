@@ -0,0 +1,699 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::{Error, Result};
+
+// Tags whose value is the offset of another directory
+const EXIF_POINTER: u16 = 0x8769;
+const GPS_POINTER: u16 = 0x8825;
+const INTEROPERABILITY_POINTER: u16 = 0xa005;
+// Offset and length of the JPEG thumbnail described by IFD1
+const THUMBNAIL_OFFSET: u16 = 0x0201;
+const THUMBNAIL_LENGTH: u16 = 0x0202;
+
+/// The directories of an EXIF payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directory {
+    /// IFD0, describing the main image
+    Primary,
+    Exif,
+    Gps,
+    Interoperability,
+    /// IFD1, describing the thumbnail
+    Thumbnail,
+}
+
+impl Directory {
+    fn from_pointer(tag: u16) -> Option<Directory> {
+        match tag {
+            EXIF_POINTER => Some(Directory::Exif),
+            GPS_POINTER => Some(Directory::Gps),
+            INTEROPERABILITY_POINTER => Some(Directory::Interoperability),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Directory {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let text = match self {
+            Directory::Primary => "IFD0",
+            Directory::Exif => "Exif",
+            Directory::Gps => "GPS",
+            Directory::Interoperability => "Interop",
+            Directory::Thumbnail => "IFD1",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// One tag of a directory. The value is kept as stored, in the payload's byte
+/// order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub tag: u16,
+    pub field_type: u16,
+    pub count: u32,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ifd {
+    pub directory: Directory,
+    pub entries: Vec<Entry>,
+    /// Directories that this one points to
+    pub children: Vec<Ifd>,
+}
+
+/// Groups of tags that `remove` can drop together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TagGroup {
+    /// The whole GPS directory
+    Gps,
+    /// Camera body and lens serial numbers and the unique image ID
+    Serials,
+    /// Capture, digitizing and modification dates and times
+    Timestamps,
+    /// Artist, camera owner and author names
+    Owner,
+    /// IFD1 and the thumbnail image
+    Thumbnail,
+}
+
+impl TagGroup {
+    pub fn contains(&self, directory: Directory, tag: u16) -> bool {
+        match self {
+            TagGroup::Gps => directory == Directory::Gps || tag == GPS_POINTER,
+            TagGroup::Serials => directory != Directory::Gps && [0xa420, 0xa431, 0xa435, 0xc62f].contains(&tag),
+            TagGroup::Timestamps => match directory {
+                Directory::Gps => [0x0007, 0x001d].contains(&tag),
+                _ => [0x0132, 0x9003, 0x9004, 0x9010, 0x9011, 0x9012, 0x9290, 0x9291, 0x9292].contains(&tag),
+            },
+            TagGroup::Owner => directory != Directory::Gps && [0x013b, 0xa430, 0x9c9d].contains(&tag),
+            TagGroup::Thumbnail => directory == Directory::Thumbnail,
+        }
+    }
+}
+
+/// A parsed eXIf chunk: a TIFF header and its directories.
+///
+/// Values that hold offsets into the payload, such as many maker notes, are
+/// copied as they are and may not survive a rewrite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exif {
+    big_endian: bool,
+    primary: Ifd,
+    thumbnail: Option<(Ifd, Vec<u8>)>,
+}
+
+impl Exif {
+    pub const CHUNK_TYPE: &'static str = "eXIf";
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let big_endian = match data.get(..4) {
+            Some(b"II*\0") => false,
+            Some(b"MM\0*") => true,
+            _ => return Err(Box::new(ExifError::Header)),
+        };
+        let reader = Reader { data, big_endian };
+        let offset = reader.u32_at(4)? as usize;
+        let mut visited = vec![];
+        let (primary, next) = reader.ifd(Directory::Primary, offset, &mut visited)?;
+        let thumbnail = match next {
+            0 => None,
+            next => {
+                let (ifd, _) = reader.ifd(Directory::Thumbnail, next as usize, &mut visited)?;
+                let value = |tag: u16| ifd.entries.iter().find(|e| e.tag == tag).map(|e| reader.number(e, 0));
+                let image = match (value(THUMBNAIL_OFFSET), value(THUMBNAIL_LENGTH)) {
+                    (Some(start), Some(length)) => reader.bytes(start as usize, length as usize)?.to_vec(),
+                    _ => vec![],
+                };
+                Some((ifd, image))
+            },
+        };
+        Ok(Exif { big_endian, primary, thumbnail })
+    }
+
+    /// Every tag with the directory it is in, in file order, leaving out the
+    /// pointers that link directories.
+    pub fn tags(&self) -> Vec<(Directory, &Entry)> {
+        fn collect<'a>(ifd: &'a Ifd, tags: &mut Vec<(Directory, &'a Entry)>) {
+            for entry in ifd.entries.iter() {
+                match ifd.children.iter().find(|c| Directory::from_pointer(entry.tag) == Some(c.directory)) {
+                    Some(child) => collect(child, tags),
+                    None => tags.push((ifd.directory, entry)),
+                }
+            }
+        }
+        let mut tags = vec![];
+        collect(&self.primary, &mut tags);
+        if let Some((ifd, _)) = &self.thumbnail {
+            collect(ifd, &mut tags);
+        }
+        tags
+    }
+
+    /// Removes every tag a predicate picks, along with the directory a picked
+    /// pointer leads to, and returns the directory and name of each. The
+    /// thumbnail goes when its offset tag does.
+    pub fn remove<F: Fn(Directory, u16) -> bool>(&mut self, picks: F) -> Vec<String> {
+        fn prune<F: Fn(Directory, u16) -> bool>(ifd: &mut Ifd, picks: &F, removed: &mut Vec<String>) {
+            let directory = ifd.directory;
+            let mut kept = vec![];
+            for entry in ifd.entries.drain(..) {
+                if !picks(directory, entry.tag) {
+                    kept.push(entry);
+                    continue;
+                }
+                match Directory::from_pointer(entry.tag) {
+                    Some(child) if ifd.children.iter().any(|c| c.directory == child) => {
+                        removed.push(format!("{} directory", child));
+                        ifd.children.retain(|c| c.directory != child);
+                    },
+                    _ => removed.push(format!("{} {}", directory, tag_label(directory, entry.tag))),
+                }
+            }
+            ifd.entries = kept;
+            for child in ifd.children.iter_mut() {
+                prune(child, picks, removed);
+            }
+        }
+        let mut removed = vec![];
+        prune(&mut self.primary, &picks, &mut removed);
+        if let Some((ifd, image)) = self.thumbnail.as_mut() {
+            prune(ifd, &picks, &mut removed);
+            if !ifd.entries.iter().any(|e| e.tag == THUMBNAIL_OFFSET) {
+                image.clear();
+            }
+        }
+        if self.thumbnail.as_ref().is_some_and(|(ifd, _)| ifd.entries.is_empty()) {
+            self.thumbnail = None;
+        }
+        removed
+    }
+
+    /// Lays the directories out again, each followed by the values that do not
+    /// fit in its entries, with every offset updated.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let writer = Writer { big_endian: self.big_endian };
+        let mut out = match self.big_endian {
+            true => b"MM\0*".to_vec(),
+            false => b"II*\0".to_vec(),
+        };
+        out.extend(writer.u32(8));
+        let next_field = writer.ifd(&self.primary, &mut out, None);
+        if let Some((ifd, image)) = &self.thumbnail {
+            let offset = out.len() as u32;
+            out[next_field..next_field + 4].copy_from_slice(&writer.u32(offset));
+            writer.ifd(ifd, &mut out, Some(image));
+        }
+        out
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE).unwrap(), self.to_bytes())
+    }
+
+    /// A tag's value as text, or as a short hex dump if it is opaque.
+    pub fn format_value(&self, entry: &Entry) -> String {
+        let reader = Reader { data: &entry.value, big_endian: self.big_endian };
+        let count = entry.count as usize;
+        let shown = count.min(8);
+        let mut values: Vec<String> = match entry.field_type {
+            2 => return format!("\"{}\"", String::from_utf8_lossy(&entry.value).trim_end_matches('\0')),
+            1 | 3 | 4 => (0..shown).map(|i| reader.number(entry, i).to_string()).collect(),
+            6 => entry.value.iter().take(shown).map(|&b| (b as i8).to_string()).collect(),
+            8 => (0..shown).map(|i| (reader.number(entry, i) as u16 as i16).to_string()).collect(),
+            9 => (0..shown).map(|i| (reader.number(entry, i) as u32 as i32).to_string()).collect(),
+            5 | 10 => (0..shown)
+                .map(|i| {
+                    let (numerator, denominator) = (reader.word(i * 8), reader.word(i * 8 + 4));
+                    match entry.field_type {
+                        5 => format!("{}/{}", numerator, denominator),
+                        _ => format!("{}/{}", numerator as i32, denominator as i32),
+                    }
+                })
+                .collect(),
+            _ if entry.value.len() <= 16 => return entry.value.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" "),
+            _ => return format!("{} bytes", entry.value.len()),
+        };
+        if count > shown {
+            values.push(format!("... {} values", count));
+        }
+        values.join(", ")
+    }
+}
+
+impl TryFrom<&Chunk> for Exif {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        Exif::parse(chunk.data())
+    }
+}
+
+impl Display for Exif {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let lines: Vec<String> = self
+            .tags()
+            .iter()
+            .map(|(directory, entry)| format!("{:<7}  0x{:04x}  {:<26}  {}", directory.to_string(), entry.tag, tag_label(*directory, entry.tag), self.format_value(entry)))
+            .collect();
+        match self.thumbnail.as_ref() {
+            Some((_, image)) if !image.is_empty() => write!(f, "{}\nThumbnail: {} bytes", lines.join("\n"), image.len()),
+            _ => write!(f, "{}", lines.join("\n")),
+        }
+    }
+}
+
+// Bytes per value of each TIFF field type
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn bytes(&self, offset: usize, length: usize) -> Result<&[u8]> {
+        match offset.checked_add(length).and_then(|end| self.data.get(offset..end)) {
+            Some(b) => Ok(b),
+            None => Err(Box::new(ExifError::Truncated(offset))),
+        }
+    }
+
+    fn u16_at(&self, offset: usize) -> Result<u16> {
+        let b = self.bytes(offset, 2)?;
+        Ok(if self.big_endian { u16::from_be_bytes([b[0], b[1]]) } else { u16::from_le_bytes([b[0], b[1]]) })
+    }
+
+    fn u32_at(&self, offset: usize) -> Result<u32> {
+        let b = self.bytes(offset, 4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if self.big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    }
+
+    // A 32-bit word of a value already known to be long enough
+    fn word(&self, offset: usize) -> u32 {
+        self.u32_at(offset).unwrap_or(0)
+    }
+
+    // Value `i` of a BYTE, SHORT or LONG entry
+    fn number(&self, entry: &Entry, i: usize) -> u64 {
+        let reader = Reader { data: &entry.value, big_endian: self.big_endian };
+        match entry.field_type {
+            1 | 6 => entry.value.get(i).map_or(0, |&b| b as u64),
+            3 | 8 => reader.u16_at(i * 2).map_or(0, |v| v as u64),
+            _ => reader.u32_at(i * 4).map_or(0, |v| v as u64),
+        }
+    }
+
+    // Reads a directory and the directories it points to, returning it with
+    // the offset of the next directory in the chain
+    fn ifd(&self, directory: Directory, offset: usize, visited: &mut Vec<usize>) -> Result<(Ifd, u32)> {
+        if visited.contains(&offset) {
+            return Err(Box::new(ExifError::Loop(offset)));
+        }
+        visited.push(offset);
+        let count = self.u16_at(offset)? as usize;
+        let mut ifd = Ifd { directory, entries: vec![], children: vec![] };
+        for i in 0..count {
+            let at = offset + 2 + i * 12;
+            let (tag, field_type, count) = (self.u16_at(at)?, self.u16_at(at + 2)?, self.u32_at(at + 4)?);
+            let size = match type_size(field_type) {
+                Some(s) => s * count as usize,
+                None => return Err(Box::new(ExifError::FieldType(tag, field_type))),
+            };
+            let value = match size {
+                0..=4 => self.bytes(at + 8, size)?,
+                _ => self.bytes(self.u32_at(at + 8)? as usize, size)?,
+            };
+            let entry = Entry { tag, field_type, count, value: value.to_vec() };
+            if let Some(child) = Directory::from_pointer(tag) {
+                let (child, _) = self.ifd(child, self.number(&entry, 0) as usize, visited)?;
+                ifd.children.push(child);
+            }
+            ifd.entries.push(entry);
+        }
+        let next = self.u32_at(offset + 2 + count * 12)?;
+        Ok((ifd, next))
+    }
+}
+
+struct Writer {
+    big_endian: bool,
+}
+
+impl Writer {
+    fn u16(&self, value: u16) -> [u8; 2] {
+        if self.big_endian { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+
+    fn u32(&self, value: u32) -> [u8; 4] {
+        if self.big_endian { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+
+    // Appends a directory, its out-of-line values and its children, returning
+    // the position of its next-directory field
+    fn ifd(&self, ifd: &Ifd, out: &mut Vec<u8>, thumbnail: Option<&Vec<u8>>) -> usize {
+        let start = out.len();
+        let mut entries: Vec<&Entry> = ifd.entries.iter().collect();
+        entries.sort_by_key(|e| e.tag);
+        out.extend(self.u16(entries.len() as u16));
+        out.resize(start + 2 + entries.len() * 12 + 4, 0);
+
+        for (i, entry) in entries.iter().enumerate() {
+            let at = start + 2 + i * 12;
+            let child = ifd.children.iter().find(|c| Directory::from_pointer(entry.tag) == Some(c.directory));
+            let field = match (child, entry.tag, thumbnail) {
+                (Some(child), _, _) => {
+                    let offset = out.len() as u32;
+                    self.ifd(child, out, None);
+                    self.u32(offset)
+                },
+                (None, THUMBNAIL_OFFSET, Some(image)) => {
+                    let offset = out.len() as u32;
+                    out.extend(image);
+                    self.u32(offset)
+                },
+                (None, _, _) if entry.value.len() <= 4 => {
+                    let mut inline = [0; 4];
+                    inline[..entry.value.len()].copy_from_slice(&entry.value);
+                    inline
+                },
+                (None, _, _) => {
+                    let offset = out.len() as u32;
+                    out.extend(&entry.value);
+                    self.u32(offset)
+                },
+            };
+            // Offsets should be even
+            if out.len() % 2 == 1 {
+                out.push(0);
+            }
+            out[at..at + 2].copy_from_slice(&self.u16(entry.tag));
+            out[at + 2..at + 4].copy_from_slice(&self.u16(entry.field_type));
+            out[at + 4..at + 8].copy_from_slice(&self.u32(entry.count));
+            out[at + 8..at + 12].copy_from_slice(&field);
+        }
+        start + 2 + entries.len() * 12
+    }
+}
+
+/// A tag's name, or its number if it is not a common one.
+pub fn tag_label(directory: Directory, tag: u16) -> String {
+    match tag_name(directory, tag) {
+        Some(name) => name.to_string(),
+        None => format!("Tag 0x{:04x}", tag),
+    }
+}
+
+fn tag_name(directory: Directory, tag: u16) -> Option<&'static str> {
+    let names: &[(u16, &str)] = match directory {
+        Directory::Gps => &GPS_TAGS,
+        Directory::Interoperability => &[(0x0001, "InteroperabilityIndex"), (0x0002, "InteroperabilityVersion")],
+        _ => &TIFF_TAGS,
+    };
+    names.iter().find(|(t, _)| *t == tag).map(|(_, name)| *name)
+}
+
+/// Finds a tag by name, ignoring case, or by number such as 0x010f.
+pub fn parse_tag(value: &str) -> Option<u16> {
+    if let Some(hex) = value.strip_prefix("0x") {
+        return u16::from_str_radix(hex, 16).ok();
+    }
+    TIFF_TAGS.iter().chain(GPS_TAGS.iter()).find(|(_, name)| name.eq_ignore_ascii_case(value)).map(|(tag, _)| *tag)
+}
+
+const TIFF_TAGS: [(u16, &str); 72] = [
+    (0x0100, "ImageWidth"),
+    (0x0101, "ImageLength"),
+    (0x0102, "BitsPerSample"),
+    (0x0103, "Compression"),
+    (0x0106, "PhotometricInterpretation"),
+    (0x010e, "ImageDescription"),
+    (0x010f, "Make"),
+    (0x0110, "Model"),
+    (0x0111, "StripOffsets"),
+    (0x0112, "Orientation"),
+    (0x0115, "SamplesPerPixel"),
+    (0x011a, "XResolution"),
+    (0x011b, "YResolution"),
+    (0x0128, "ResolutionUnit"),
+    (0x0131, "Software"),
+    (0x0132, "DateTime"),
+    (0x013b, "Artist"),
+    (0x013e, "WhitePoint"),
+    (0x013f, "PrimaryChromaticities"),
+    (0x0201, "JPEGInterchangeFormat"),
+    (0x0202, "JPEGInterchangeFormatLength"),
+    (0x0211, "YCbCrCoefficients"),
+    (0x0213, "YCbCrPositioning"),
+    (0x0214, "ReferenceBlackWhite"),
+    (0x8298, "Copyright"),
+    (0x829a, "ExposureTime"),
+    (0x829d, "FNumber"),
+    (0x8769, "ExifIFDPointer"),
+    (0x8822, "ExposureProgram"),
+    (0x8825, "GPSInfoIFDPointer"),
+    (0x8827, "ISOSpeedRatings"),
+    (0x8830, "SensitivityType"),
+    (0x9000, "ExifVersion"),
+    (0x9003, "DateTimeOriginal"),
+    (0x9004, "DateTimeDigitized"),
+    (0x9010, "OffsetTime"),
+    (0x9011, "OffsetTimeOriginal"),
+    (0x9012, "OffsetTimeDigitized"),
+    (0x9101, "ComponentsConfiguration"),
+    (0x9201, "ShutterSpeedValue"),
+    (0x9202, "ApertureValue"),
+    (0x9203, "BrightnessValue"),
+    (0x9204, "ExposureBiasValue"),
+    (0x9205, "MaxApertureValue"),
+    (0x9207, "MeteringMode"),
+    (0x9208, "LightSource"),
+    (0x9209, "Flash"),
+    (0x920a, "FocalLength"),
+    (0x927c, "MakerNote"),
+    (0x9286, "UserComment"),
+    (0x9290, "SubSecTime"),
+    (0x9291, "SubSecTimeOriginal"),
+    (0x9292, "SubSecTimeDigitized"),
+    (0x9c9d, "XPAuthor"),
+    (0xa000, "FlashpixVersion"),
+    (0xa001, "ColorSpace"),
+    (0xa002, "PixelXDimension"),
+    (0xa003, "PixelYDimension"),
+    (0xa005, "InteroperabilityIFDPointer"),
+    (0xa402, "ExposureMode"),
+    (0xa403, "WhiteBalance"),
+    (0xa405, "FocalLengthIn35mmFilm"),
+    (0xa406, "SceneCaptureType"),
+    (0xa420, "ImageUniqueID"),
+    (0xa430, "CameraOwnerName"),
+    (0xa431, "BodySerialNumber"),
+    (0xa432, "LensSpecification"),
+    (0xa433, "LensMake"),
+    (0xa434, "LensModel"),
+    (0xa435, "LensSerialNumber"),
+    (0xa460, "CompositeImage"),
+    (0xc62f, "CameraSerialNumber"),
+];
+
+const GPS_TAGS: [(u16, &str); 20] = [
+    (0x0000, "GPSVersionID"),
+    (0x0001, "GPSLatitudeRef"),
+    (0x0002, "GPSLatitude"),
+    (0x0003, "GPSLongitudeRef"),
+    (0x0004, "GPSLongitude"),
+    (0x0005, "GPSAltitudeRef"),
+    (0x0006, "GPSAltitude"),
+    (0x0007, "GPSTimeStamp"),
+    (0x0008, "GPSSatellites"),
+    (0x0009, "GPSStatus"),
+    (0x000a, "GPSMeasureMode"),
+    (0x000b, "GPSDOP"),
+    (0x000c, "GPSSpeedRef"),
+    (0x000d, "GPSSpeed"),
+    (0x0010, "GPSImgDirectionRef"),
+    (0x0011, "GPSImgDirection"),
+    (0x0012, "GPSMapDatum"),
+    (0x001b, "GPSProcessingMethod"),
+    (0x001d, "GPSDateStamp"),
+    (0x001f, "GPSHPositioningError"),
+];
+
+
+
+#[derive(Debug)]
+pub enum ExifError {
+    Header,
+    Truncated(usize),
+    FieldType(u16, u16),
+    Loop(usize),
+}
+impl Display for ExifError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ExifError::Header => write!(f, "The EXIF data does not start with a TIFF header."),
+            ExifError::Truncated(offset) => write!(f, "The EXIF data ends before offset {}.", offset),
+            ExifError::FieldType(tag, t) => write!(f, "Tag 0x{:04x} has unknown field type {}.", tag, t),
+            ExifError::Loop(offset) => write!(f, "The EXIF directory at offset {} is linked more than once.", offset),
+        }
+    }
+}
+impl std::error::Error for ExifError {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A little-endian payload laid out by hand: IFD0 holds Make, Orientation
+    // and a GPS pointer, and the GPS directory holds GPSLatitudeRef
+    fn handmade() -> Vec<u8> {
+        let mut data = b"II*\0".to_vec();
+        data.extend(8u32.to_le_bytes());
+        data.extend(3u16.to_le_bytes());
+        for (tag, field_type, count, value) in [(0x010fu16, 2u16, 6u32, 50u32), (0x0112, 3, 1, 1), (GPS_POINTER, 4, 1, 56)] {
+            data.extend(tag.to_le_bytes());
+            data.extend(field_type.to_le_bytes());
+            data.extend(count.to_le_bytes());
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(0u32.to_le_bytes());
+        data.extend(b"Canon\0");
+        data.extend(1u16.to_le_bytes());
+        data.extend([1, 0, 2, 0, 2, 0, 0, 0, b'N', 0, 0, 0]);
+        data.extend(0u32.to_le_bytes());
+        data
+    }
+
+    fn entry(tag: u16, field_type: u16, count: u32, value: &[u8]) -> Entry {
+        Entry { tag, field_type, count, value: value.to_vec() }
+    }
+
+    // A big-endian payload with an Exif, GPS and thumbnail directory
+    fn camera_exif() -> Exif {
+        let exif = Ifd {
+            directory: Directory::Exif,
+            entries: vec![
+                entry(0x829a, 5, 1, &[0, 0, 0, 1, 0, 0, 0, 200]),
+                entry(0x9003, 2, 20, b"2024:05:01 12:30:00\0"),
+                entry(0xa431, 2, 8, b"0123456\0"),
+            ],
+            children: vec![],
+        };
+        let gps = Ifd {
+            directory: Directory::Gps,
+            entries: vec![entry(0x0001, 2, 2, b"S\0"), entry(0x0002, 5, 3, &[0, 0, 0, 33, 0, 0, 0, 1, 0, 0, 0, 52, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1])],
+            children: vec![],
+        };
+        let primary = Ifd {
+            directory: Directory::Primary,
+            entries: vec![
+                entry(0x010f, 2, 5, b"Sony\0"),
+                entry(0x0132, 2, 20, b"2024:05:02 08:00:00\0"),
+                entry(EXIF_POINTER, 4, 1, &[0; 4]),
+                entry(GPS_POINTER, 4, 1, &[0; 4]),
+            ],
+            children: vec![exif, gps],
+        };
+        let thumbnail = Ifd {
+            directory: Directory::Thumbnail,
+            entries: vec![entry(THUMBNAIL_OFFSET, 4, 1, &[0; 4]), entry(THUMBNAIL_LENGTH, 4, 1, &[0, 0, 0, 3])],
+            children: vec![],
+        };
+        Exif { big_endian: true, primary, thumbnail: Some((thumbnail, vec![0xff, 0xd8, 0xff])) }
+    }
+
+    fn tag_names(exif: &Exif) -> Vec<String> {
+        exif.tags().iter().map(|(directory, entry)| tag_label(*directory, entry.tag)).collect()
+    }
+
+    #[test]
+    fn test_parse_handmade() {
+        let exif = Exif::parse(&handmade()).unwrap();
+        assert_eq!(tag_names(&exif), vec!["Make", "Orientation", "GPSLatitudeRef"]);
+        let tags = exif.tags();
+        assert_eq!(exif.format_value(tags[0].1), "\"Canon\"");
+        assert_eq!(exif.format_value(tags[1].1), "1");
+        assert_eq!(tags[2].0, Directory::Gps);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let exif = camera_exif();
+        let parsed = Exif::parse(&exif.to_bytes()).unwrap();
+        assert_eq!(tag_names(&parsed), tag_names(&exif));
+        assert_eq!(parsed.thumbnail.as_ref().unwrap().1, vec![0xff, 0xd8, 0xff]);
+        assert_eq!(parsed.to_bytes(), exif.to_bytes());
+
+        let handmade = Exif::parse(&handmade()).unwrap();
+        assert_eq!(Exif::parse(&handmade.to_bytes()).unwrap(), handmade);
+    }
+
+    #[test]
+    fn test_format_values() {
+        let exif = camera_exif();
+        let tags = exif.tags();
+        let value = |name: &str| {
+            let (_, entry) = tags.iter().find(|(d, e)| tag_label(*d, e.tag) == name).unwrap();
+            exif.format_value(entry)
+        };
+        assert_eq!(value("ExposureTime"), "1/200");
+        assert_eq!(value("GPSLatitude"), "33/1, 52/1, 0/1");
+        assert!(exif.to_string().contains("Thumbnail: 3 bytes"));
+    }
+
+    #[test]
+    fn test_remove_groups() {
+        let mut exif = camera_exif();
+        let removed = exif.remove(|d, t| TagGroup::Gps.contains(d, t) || TagGroup::Serials.contains(d, t));
+        assert_eq!(removed, vec!["GPS directory", "Exif BodySerialNumber"]);
+        let parsed = Exif::parse(exif.as_chunk().data()).unwrap();
+        assert_eq!(tag_names(&parsed), vec!["Make", "DateTime", "ExposureTime", "DateTimeOriginal", "JPEGInterchangeFormat", "JPEGInterchangeFormatLength"]);
+
+        exif.remove(|d, t| TagGroup::Timestamps.contains(d, t) || TagGroup::Thumbnail.contains(d, t));
+        assert_eq!(tag_names(&exif), vec!["Make", "ExposureTime"]);
+        assert!(exif.thumbnail.is_none());
+    }
+
+    #[test]
+    fn test_as_chunk() {
+        let chunk = camera_exif().as_chunk();
+        assert_eq!(chunk.chunk_type().to_string(), "eXIf");
+        assert_eq!(Exif::try_from(&chunk).unwrap(), Exif::parse(chunk.data()).unwrap());
+    }
+
+    #[test]
+    fn test_parse_tag() {
+        assert_eq!(parse_tag("Make"), Some(0x010f));
+        assert_eq!(parse_tag("bodyserialnumber"), Some(0xa431));
+        assert_eq!(parse_tag("0x9003"), Some(0x9003));
+        assert_eq!(parse_tag("Nonsense"), None);
+    }
+
+    #[test]
+    fn test_invalid_payloads() {
+        assert!(Exif::parse(b"JFIF").is_err());
+        let data = handmade();
+        assert!(Exif::parse(&data[..40]).is_err());
+        // IFD0 pointing at itself as its GPS directory
+        let mut looped = data.clone();
+        looped[42..46].copy_from_slice(&8u32.to_le_bytes());
+        assert!(Exif::parse(&looped).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chunk_type::ChunkType;
+use crate::registry;
+
+/// What each case bit of a chunk type means, and what the type is for if it
+/// is registered.
+pub struct Explanation {
+    pub chunk_type: ChunkType,
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let chunk_type = &self.chunk_type;
+        let bits = [
+            (
+                chunk_type.is_critical(),
+                "critical: a decoder that does not understand it must refuse the file",
+                "ancillary: a decoder that does not understand it can ignore it",
+            ),
+            (
+                chunk_type.is_public(),
+                "public: reserved for types registered in the PNG specification",
+                "private: free for applications to define",
+            ),
+            (
+                chunk_type.is_reserved_bit_valid(),
+                "reserved bit valid: this letter must be uppercase",
+                "reserved bit set: no current PNG decoder accepts this",
+            ),
+            (
+                chunk_type.is_safe_to_copy(),
+                "safe to copy: editors may keep it even after changing the critical chunks",
+                "unsafe to copy: it depends on the image data, so editors must drop it when changing the critical chunks",
+            ),
+        ];
+
+        writeln!(f, "{}", chunk_type)?;
+        for (byte, (set, yes, no)) in chunk_type.bytes().iter().zip(bits) {
+            // The property holds when the letter is uppercase, except for safe to copy
+            let case = if byte.is_ascii_uppercase() { "uppercase" } else { "lowercase" };
+            writeln!(f, "  {}  {:<9}  {}", *byte as char, case, if set { yes } else { no })?;
+        }
+        writeln!(f, "Valid: {}", if chunk_type.is_valid() { "yes" } else { "no, the third letter must be uppercase" })?;
+
+        match registry::lookup(&chunk_type.to_string()) {
+            Some(info) => {
+                writeln!(f, "Registered: yes, in the {}", info.source)?;
+                writeln!(f, "  {}: {}", info.name, info.description)?;
+                writeln!(f, "  Placement: {}", info.placement)?;
+                write!(f, "  Per file: {}", info.multiplicity)
+            },
+            None => write!(f, "Registered: no"),
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn explain(chunk_type: &str) -> String {
+        Explanation { chunk_type: ChunkType::from_str(chunk_type).unwrap() }.to_string()
+    }
+
+    #[test]
+    fn test_private_type() {
+        let output = explain("ruSt");
+        assert!(output.contains("  r  lowercase  ancillary"));
+        assert!(output.contains("  u  lowercase  private"));
+        assert!(output.contains("  t  lowercase  safe to copy"));
+        assert!(output.contains("Valid: yes"));
+        assert!(output.ends_with("Registered: no"));
+    }
+
+    #[test]
+    fn test_invalid_type() {
+        let output = explain("Rust");
+        assert!(output.contains("reserved bit set"));
+        assert!(output.contains("Valid: no"));
+    }
+
+    #[test]
+    fn test_registered_type() {
+        let output = explain("gAMA");
+        assert!(output.contains("unsafe to copy"));
+        assert!(output.contains("Registered: yes, in the PNG specification"));
+        assert!(output.contains("Image gamma"));
+        assert!(output.contains("Placement: before PLTE and IDAT"));
+        assert!(output.contains("Per file: at most one"));
+    }
+}
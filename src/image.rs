@@ -0,0 +1,533 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::{Error, Result};
+
+// Starting column, starting row, column step and row step of each Adam7 pass
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColourType {
+    Greyscale,
+    Truecolour,
+    Indexed,
+    GreyscaleAlpha,
+    TruecolourAlpha,
+}
+
+impl ColourType {
+    pub fn code(&self) -> u8 {
+        match self {
+            ColourType::Greyscale => 0,
+            ColourType::Truecolour => 2,
+            ColourType::Indexed => 3,
+            ColourType::GreyscaleAlpha => 4,
+            ColourType::TruecolourAlpha => 6,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            ColourType::Greyscale | ColourType::Indexed => 1,
+            ColourType::GreyscaleAlpha => 2,
+            ColourType::Truecolour => 3,
+            ColourType::TruecolourAlpha => 4,
+        }
+    }
+
+    pub fn channel_names(&self) -> &'static [&'static str] {
+        match self {
+            ColourType::Greyscale => &["grey"],
+            ColourType::Indexed => &["index"],
+            ColourType::GreyscaleAlpha => &["grey", "alpha"],
+            ColourType::Truecolour => &["red", "green", "blue"],
+            ColourType::TruecolourAlpha => &["red", "green", "blue", "alpha"],
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, ColourType::GreyscaleAlpha | ColourType::TruecolourAlpha)
+    }
+
+    pub fn allows_bit_depth(&self, bit_depth: u8) -> bool {
+        match self {
+            ColourType::Greyscale => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+            ColourType::Indexed => matches!(bit_depth, 1 | 2 | 4 | 8),
+            _ => matches!(bit_depth, 8 | 16),
+        }
+    }
+}
+
+impl TryFrom<u8> for ColourType {
+    type Error = ();
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ColourType::Greyscale),
+            2 => Ok(ColourType::Truecolour),
+            3 => Ok(ColourType::Indexed),
+            4 => Ok(ColourType::GreyscaleAlpha),
+            6 => Ok(ColourType::TruecolourAlpha),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for ColourType {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let name = match self {
+            ColourType::Greyscale => "greyscale",
+            ColourType::Truecolour => "truecolour",
+            ColourType::Indexed => "indexed-colour",
+            ColourType::GreyscaleAlpha => "greyscale with alpha",
+            ColourType::TruecolourAlpha => "truecolour with alpha",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The typed contents of an `IHDR` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub colour_type: ColourType,
+    pub interlaced: bool,
+}
+
+impl ImageHeader {
+    pub const CHUNK_TYPE: &'static str = "IHDR";
+
+    pub fn from_png(png: &Png) -> Result<Self> {
+        match png.chunk_by_type(Self::CHUNK_TYPE) {
+            Some(chunk) => ImageHeader::try_from(chunk),
+            None => Err(Box::new(ImageError::MissingHeader)),
+        }
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.colour_type.channels() * self.bit_depth as usize
+    }
+
+    /// Number of bytes the filter algorithms treat as one pixel (never less than one).
+    fn filter_stride(&self) -> usize {
+        (self.bits_per_pixel() / 8).max(1)
+    }
+
+    fn row_bytes(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Length of the decompressed image data, with a filter byte per row of
+    /// every pass, or None if it would not fit in memory.
+    fn filtered_len(&self) -> Option<usize> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let passes: Vec<(usize, usize)> = match self.interlaced {
+            true => ADAM7_PASSES
+                .iter()
+                .filter(|&&(x0, y0, _, _)| width > x0 && height > y0)
+                .map(|&(x0, y0, dx, dy)| ((width - x0).div_ceil(dx), (height - y0).div_ceil(dy)))
+                .collect(),
+            false => vec![(width, height)],
+        };
+        passes.iter().try_fold(0usize, |total, &(w, h)| {
+            let row = w.checked_mul(self.bits_per_pixel())?.div_ceil(8).checked_add(1)?;
+            total.checked_add(row.checked_mul(h)?)
+        })
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(13);
+        data.extend(self.width.to_be_bytes());
+        data.extend(self.height.to_be_bytes());
+        data.push(self.bit_depth);
+        data.push(self.colour_type.code());
+        data.push(0);
+        data.push(0);
+        data.push(self.interlaced as u8);
+        Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE).unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for ImageHeader {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        if chunk.chunk_type().to_string() != Self::CHUNK_TYPE || data.len() != 13 {
+            return Err(Box::new(ImageError::InvalidHeader));
+        }
+        let width = u32::from_be_bytes(data[0..4].try_into()?);
+        let height = u32::from_be_bytes(data[4..8].try_into()?);
+        let bit_depth = data[8];
+        let colour_type = match ColourType::try_from(data[9]) {
+            Ok(c) => c,
+            Err(_) => return Err(Box::new(ImageError::InvalidHeader)),
+        };
+        let (compression, filter, interlace) = (data[10], data[11], data[12]);
+        if width == 0 || height == 0 || !colour_type.allows_bit_depth(bit_depth) || compression != 0 || filter != 0 || interlace > 1 {
+            return Err(Box::new(ImageError::InvalidHeader));
+        }
+        Ok(ImageHeader { width, height, bit_depth, colour_type, interlaced: interlace == 1 })
+    }
+}
+
+/// Decoded pixel data. Samples are stored row by row with the channels of each
+/// pixel interleaved, at their original bit depth.
+pub struct Image {
+    header: ImageHeader,
+    samples: Vec<u16>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, bit_depth: u8, colour_type: ColourType, samples: Vec<u16>) -> Result<Self> {
+        let header = ImageHeader { width, height, bit_depth, colour_type, interlaced: false };
+        if !colour_type.allows_bit_depth(bit_depth) {
+            return Err(Box::new(ImageError::InvalidHeader));
+        }
+        if samples.len() != width as usize * height as usize * colour_type.channels() {
+            return Err(Box::new(ImageError::SampleCount(samples.len())));
+        }
+        Ok(Image { header, samples })
+    }
+
+    pub fn decode(png: &Png) -> Result<Self> {
+        let header = ImageHeader::from_png(png)?;
+        let compressed: Vec<u8> = png
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().to_string() == "IDAT")
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect();
+        if compressed.is_empty() {
+            return Err(Box::new(ImageError::MissingData));
+        }
+        let (width, height) = (header.width as usize, header.height as usize);
+        let channels = header.colour_type.channels();
+        let sizes = (header.filtered_len(), width.checked_mul(height).and_then(|n| n.checked_mul(channels)));
+        let (filtered_len, sample_count) = match sizes {
+            (Some(f), Some(s)) => (f, s),
+            _ => return Err(Box::new(ImageError::TooLarge(header.width, header.height))),
+        };
+        // Inflating no more than the header calls for stops zlib bombs
+        let filtered = match decompress_to_vec_zlib_with_limit(&compressed, filtered_len) {
+            Ok(f) => f,
+            Err(_) => return Err(Box::new(ImageError::Compression)),
+        };
+        if filtered.len() < filtered_len {
+            return Err(Box::new(ImageError::TruncatedData));
+        }
+
+        let mut samples = vec![0; sample_count];
+        if header.interlaced {
+            let mut offset = 0;
+            for (x0, y0, dx, dy) in ADAM7_PASSES {
+                if width <= x0 || height <= y0 {
+                    continue;
+                }
+                let pass_width = (width - x0).div_ceil(dx);
+                let pass_height = (height - y0).div_ceil(dy);
+                let (rows, consumed) = unfilter(&header, &filtered[offset..], pass_width, pass_height)?;
+                offset += consumed;
+                for (py, row) in rows.iter().enumerate() {
+                    let row_samples = unpack_row(row, header.bit_depth, pass_width * channels);
+                    for px in 0..pass_width {
+                        let target = ((y0 + py * dy) * width + x0 + px * dx) * channels;
+                        samples[target..target + channels].copy_from_slice(&row_samples[px * channels..(px + 1) * channels]);
+                    }
+                }
+            }
+        } else {
+            let (rows, _) = unfilter(&header, &filtered, width, height)?;
+            for (y, row) in rows.iter().enumerate() {
+                let row_samples = unpack_row(row, header.bit_depth, width * channels);
+                samples[y * width * channels..(y + 1) * width * channels].copy_from_slice(&row_samples);
+            }
+        }
+        Ok(Image { header, samples })
+    }
+
+    pub fn header(&self) -> &ImageHeader {
+        &self.header
+    }
+
+    pub fn samples(&self) -> &[u16] {
+        &self.samples
+    }
+
+    /// The samples of a single channel, in scan order.
+    pub fn channel(&self, channel: usize) -> Vec<u16> {
+        let channels = self.header.colour_type.channels();
+        self.samples.iter().skip(channel).step_by(channels).copied().collect()
+    }
+
+    /// Compresses the samples into zlib data suitable for `IDAT`, without interlacing.
+    pub fn compress(&self) -> Vec<u8> {
+        let width = self.header.width as usize;
+        let channels = self.header.colour_type.channels();
+        let row_bytes = self.header.row_bytes(width);
+        let mut filtered = Vec::with_capacity((row_bytes + 1) * self.header.height as usize);
+        for row in self.samples.chunks(width * channels) {
+            filtered.push(0);
+            filtered.extend(pack_row(row, self.header.bit_depth, row_bytes));
+        }
+        compress_to_vec_zlib(&filtered, 6)
+    }
+
+    /// Replaces a file's IHDR and image data with this image, putting a single
+    /// IDAT chunk where the first one was and leaving every other chunk alone.
+    pub fn store(&self, png: &mut Png) -> Result<()> {
+        let position = |png: &Png, chunk_type: &str| png.chunks().iter().position(|c| c.chunk_type().to_string() == chunk_type);
+        let header_index = match position(png, "IHDR") {
+            Some(i) => i,
+            None => return Err(Box::new(ImageError::MissingHeader)),
+        };
+        let data_index = match position(png, "IDAT") {
+            Some(i) => i,
+            None => return Err(Box::new(ImageError::MissingData)),
+        };
+        png.replace_chunk_data(header_index, self.header.as_chunk().data().to_vec())?;
+        // The first IDAT takes all the data, so the file is never left without one
+        while let Some(i) = png.chunks().iter().rposition(|c| c.chunk_type().to_string() == "IDAT").filter(|i| *i > data_index) {
+            png.remove_chunk_at(i)?;
+        }
+        png.replace_chunk_data(data_index, self.compress()).map(|_| ())
+    }
+
+    /// Builds a standalone PNG holding this image. Indexed images also need a
+    /// `PLTE` chunk, which is the caller's responsibility.
+    pub fn to_png(&self) -> Png {
+        let idat = Chunk::new(ChunkType::from_str("IDAT").unwrap(), self.compress());
+        let iend = Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]);
+        Png::from_chunks(vec![self.header.as_chunk(), idat, iend])
+    }
+}
+
+// Reverses the per-row filters, returning the raw rows and the number of bytes consumed
+fn unfilter(header: &ImageHeader, data: &[u8], width: usize, height: usize) -> Result<(Vec<Vec<u8>>, usize)> {
+    let row_bytes = header.row_bytes(width);
+    let stride = header.filter_stride();
+    if data.len() < (row_bytes + 1) * height {
+        return Err(Box::new(ImageError::TruncatedData));
+    }
+    let mut rows: Vec<Vec<u8>> = Vec::with_capacity(height);
+    let mut previous = vec![0u8; row_bytes];
+    for y in 0..height {
+        let start = y * (row_bytes + 1);
+        let filter = data[start];
+        let mut row = data[start + 1..start + 1 + row_bytes].to_vec();
+        for i in 0..row_bytes {
+            let left = if i >= stride { row[i - stride] } else { 0 };
+            let up = previous[i];
+            let upper_left = if i >= stride { previous[i - stride] } else { 0 };
+            let predictor = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, upper_left),
+                f => return Err(Box::new(ImageError::InvalidFilter(f))),
+            };
+            row[i] = row[i].wrapping_add(predictor);
+        }
+        previous = row.clone();
+        rows.push(row);
+    }
+    Ok((rows, (row_bytes + 1) * height))
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn unpack_row(row: &[u8], bit_depth: u8, count: usize) -> Vec<u16> {
+    match bit_depth {
+        16 => row.chunks(2).take(count).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect(),
+        8 => row.iter().take(count).map(|&b| b as u16).collect(),
+        depth => {
+            let per_byte = 8 / depth as usize;
+            let mask = (1u16 << depth) - 1;
+            (0..count)
+                .map(|i| {
+                    let shift = 8 - depth as usize * (i % per_byte + 1);
+                    (row[i / per_byte] as u16 >> shift) & mask
+                })
+                .collect()
+        }
+    }
+}
+
+fn pack_row(samples: &[u16], bit_depth: u8, row_bytes: usize) -> Vec<u8> {
+    match bit_depth {
+        16 => samples.iter().flat_map(|s| s.to_be_bytes()).collect(),
+        8 => samples.iter().map(|&s| s as u8).collect(),
+        depth => {
+            let per_byte = 8 / depth as usize;
+            let mut row = vec![0u8; row_bytes];
+            for (i, &sample) in samples.iter().enumerate() {
+                let shift = 8 - depth as usize * (i % per_byte + 1);
+                row[i / per_byte] |= (sample as u8) << shift;
+            }
+            row
+        }
+    }
+}
+
+
+
+#[derive(Debug)]
+pub enum ImageError {
+    MissingHeader,
+    InvalidHeader,
+    MissingData,
+    Compression,
+    TruncatedData,
+    InvalidFilter(u8),
+    SampleCount(usize),
+    TooLarge(u32, u32),
+}
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ImageError::MissingHeader => write!(f, "The image has no IHDR chunk."),
+            ImageError::InvalidHeader => write!(f, "The IHDR chunk is not valid."),
+            ImageError::MissingData => write!(f, "The image has no IDAT chunks."),
+            ImageError::Compression => write!(f, "The image data could not be decompressed."),
+            ImageError::TruncatedData => write!(f, "The image data is shorter than the IHDR chunk requires."),
+            ImageError::InvalidFilter(t) => write!(f, "{} is not a valid scanline filter type.", t),
+            ImageError::SampleCount(n) => write!(f, "{} samples do not fit the image dimensions.", n),
+            ImageError::TooLarge(width, height) => write!(f, "A {}x{} image is too large to decode.", width, height),
+        }
+    }
+}
+impl std::error::Error for ImageError {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(colour_type: ColourType, bit_depth: u8) -> Image {
+        let (width, height) = (13, 7);
+        let max = (1u32 << bit_depth) - 1;
+        let samples = (0..width * height * colour_type.channels() as u32)
+            .map(|i| ((i * 37 + i / 5) % (max + 1)) as u16)
+            .collect();
+        Image::new(width, height, bit_depth, colour_type, samples).unwrap()
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let header = ImageHeader { width: 50, height: 20, bit_depth: 16, colour_type: ColourType::TruecolourAlpha, interlaced: true };
+        let parsed = ImageHeader::try_from(&header.as_chunk()).unwrap();
+        assert_eq!(header, parsed);
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut chunk = ImageHeader { width: 1, height: 1, bit_depth: 8, colour_type: ColourType::Truecolour, interlaced: false }.as_chunk();
+        chunk.chunk_data[8] = 4;
+        assert!(ImageHeader::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_decode_refuses_oversized_data() {
+        let png = |header: ImageHeader, data: Vec<u8>| {
+            let idat = Chunk::new(ChunkType::from_str("IDAT").unwrap(), compress_to_vec_zlib(&data, 6));
+            Png::from_chunks(vec![header.as_chunk(), idat])
+        };
+        let huge = ImageHeader { width: u32::MAX, height: u32::MAX, bit_depth: 16, colour_type: ColourType::TruecolourAlpha, interlaced: false };
+        match Image::decode(&png(huge, vec![0; 16])) {
+            Err(e) => assert_eq!(e.to_string(), format!("A {0}x{0} image is too large to decode.", u32::MAX)),
+            Ok(_) => panic!("expected the image to be too large"),
+        }
+
+        let large = ImageHeader { width: 100_000, height: 100_000, ..huge };
+        assert!(Image::decode(&png(large, vec![0; 16])).is_err());
+
+        let tiny = ImageHeader { width: 1, height: 1, bit_depth: 8, colour_type: ColourType::Greyscale, interlaced: false };
+        assert!(Image::decode(&png(tiny, vec![0; 1 << 20])).is_err());
+        assert!(Image::decode(&png(tiny, vec![0; 2])).is_ok());
+    }
+
+    #[test]
+    fn test_image_round_trip() {
+        for (colour_type, bit_depth) in [
+            (ColourType::Greyscale, 1),
+            (ColourType::Greyscale, 4),
+            (ColourType::Indexed, 2),
+            (ColourType::Truecolour, 8),
+            (ColourType::GreyscaleAlpha, 16),
+            (ColourType::TruecolourAlpha, 16),
+        ] {
+            let image = gradient(colour_type, bit_depth);
+            let decoded = Image::decode(&image.to_png()).unwrap();
+            assert_eq!(decoded.samples(), image.samples());
+        }
+    }
+
+    #[test]
+    fn test_channel() {
+        let image = Image::new(2, 1, 8, ColourType::Truecolour, vec![1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(image.channel(1), vec![2, 5]);
+    }
+
+    #[test]
+    fn test_store() {
+        let mut png = gradient(ColourType::Greyscale, 8).to_png();
+        let idat = png.chunks()[1].clone();
+        png.insert_chunk(2, idat).unwrap();
+        png.insert_chunk(1, Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0, 0, 0, 1])).unwrap();
+
+        let image = gradient(ColourType::Greyscale, 4);
+        image.store(&mut png).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["IHDR", "gAMA", "IDAT", "IEND"]);
+        assert_eq!(Image::decode(&png).unwrap().samples(), image.samples());
+    }
+
+    #[test]
+    fn test_decode_interlaced_image() {
+        // Re-encodes a gradient as Adam7 by hand and checks it decodes to the same samples
+        let image = gradient(ColourType::Truecolour, 8);
+        let header = ImageHeader { interlaced: true, ..*image.header() };
+        let (width, height) = (header.width as usize, header.height as usize);
+        let mut filtered = vec![];
+        for (x0, y0, dx, dy) in ADAM7_PASSES {
+            if width <= x0 || height <= y0 {
+                continue;
+            }
+            for y in (y0..height).step_by(dy) {
+                filtered.push(0);
+                for x in (x0..width).step_by(dx) {
+                    filtered.extend(image.samples()[(y * width + x) * 3..(y * width + x) * 3 + 3].iter().map(|&s| s as u8));
+                }
+            }
+        }
+        let idat = Chunk::new(ChunkType::from_str("IDAT").unwrap(), compress_to_vec_zlib(&filtered, 6));
+        let png = Png::from_chunks(vec![header.as_chunk(), idat]);
+        assert_eq!(Image::decode(&png).unwrap().samples(), image.samples());
+    }
+}
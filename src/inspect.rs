@@ -0,0 +1,292 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chunk::Chunk;
+use crate::image::ImageHeader;
+use crate::png::RawChunk;
+use crate::registry;
+use crate::Result;
+
+const DUMP_WIDTH: usize = 16;
+
+/// A detailed, human-readable view of one chunk as it sits in a file.
+pub struct ChunkInspection<'a> {
+    pub index: usize,
+    pub raw: &'a RawChunk,
+}
+
+impl Display for ChunkInspection<'_> {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let chunk = &self.raw.chunk;
+        let chunk_type = chunk.chunk_type();
+        writeln!(f, "Chunk {} ({}) at offset {}", self.index, chunk_type, self.raw.offset)?;
+        match registry::lookup(&chunk_type.to_string()) {
+            Some(info) => {
+                writeln!(f, "  {} ({})", info.name, info.source)?;
+                writeln!(f, "  {}", info.description)?;
+            },
+            None => writeln!(f, "  Not a registered chunk type")?,
+        }
+        writeln!(f, "  Length: {}", chunk.length())?;
+        let crc_status = if self.raw.crc_matches() { String::from("ok") } else { format!("BAD, expected {:08x}", chunk.crc()) };
+        writeln!(f, "  CRC: {:08x} ({})", self.raw.stored_crc, crc_status)?;
+
+        writeln!(f, "  Type bits:")?;
+        let bytes = chunk_type.bytes();
+        let properties = [
+            (chunk_type.is_critical(), "critical", "ancillary"),
+            (chunk_type.is_public(), "public", "private"),
+            (chunk_type.is_reserved_bit_valid(), "reserved bit valid", "reserved bit INVALID"),
+            (chunk_type.is_safe_to_copy(), "safe to copy", "unsafe to copy"),
+        ];
+        for (byte, (set, yes, no)) in bytes.iter().zip(properties) {
+            writeln!(f, "    {}  {}", *byte as char, if set { yes } else { no })?;
+        }
+
+        match fields(chunk) {
+            Ok(fields) if fields.is_empty() => {},
+            Ok(fields) => {
+                writeln!(f, "  Fields:")?;
+                for (name, value) in fields {
+                    writeln!(f, "    {}: {}", name, value)?;
+                }
+            },
+            Err(e) => writeln!(f, "  Fields: {}", e)?,
+        }
+
+        writeln!(f, "  Data:")?;
+        for line in hex_dump(chunk.data(), self.raw.offset + 8).lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats bytes as a canonical hex and ASCII dump, numbering each line with
+/// `start` plus its position in `data`.
+pub fn hex_dump(data: &[u8], start: usize) -> String {
+    let mut output = String::new();
+    for (line, bytes) in data.chunks(DUMP_WIDTH).enumerate() {
+        let mut hex = String::new();
+        for i in 0..DUMP_WIDTH {
+            match bytes.get(i) {
+                Some(b) => hex.push_str(&format!("{:02x} ", b)),
+                None => hex.push_str("   "),
+            }
+            if i == DUMP_WIDTH / 2 - 1 {
+                hex.push(' ');
+            }
+        }
+        let ascii: String = bytes.iter().map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' }).collect();
+        output.push_str(&format!("{:08x}  {} |{}|\n", start + line * DUMP_WIDTH, hex, ascii));
+    }
+    output
+}
+
+/// Breaks the data of a standard chunk into named fields. Chunk types without
+/// a known layout give no fields.
+pub fn fields(chunk: &Chunk) -> Result<Vec<(String, String)>> {
+    let data = chunk.data();
+    let mut reader = FieldReader { data, position: 0 };
+    let mut fields: Vec<(String, String)> = vec![];
+    let mut add = |name: &str, value: String| fields.push((String::from(name), value));
+    match chunk.chunk_type().to_string().as_str() {
+        "IHDR" => {
+            let header = ImageHeader::try_from(chunk)?;
+            add("Width", header.width.to_string());
+            add("Height", header.height.to_string());
+            add("Bit depth", header.bit_depth.to_string());
+            add("Colour type", format!("{} ({})", header.colour_type.code(), header.colour_type));
+            add("Interlace method", if header.interlaced { String::from("1 (Adam7)") } else { String::from("0 (none)") });
+        },
+        "PLTE" => {
+            if !data.len().is_multiple_of(3) {
+                return Err(Box::new(FieldError::Length(data.len())));
+            }
+            add("Entries", (data.len() / 3).to_string());
+            for (i, rgb) in data.chunks(3).enumerate() {
+                add(&format!("Entry {}", i), format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]));
+            }
+        },
+        "gAMA" => {
+            let gamma = reader.u32()?;
+            add("Gamma", format!("{} ({:.5})", gamma, gamma as f64 / 100000.0));
+        },
+        "cHRM" => {
+            for name in ["White point", "Red", "Green", "Blue"] {
+                let (x, y) = (reader.u32()?, reader.u32()?);
+                add(name, format!("x {:.5}, y {:.5}", x as f64 / 100000.0, y as f64 / 100000.0));
+            }
+        },
+        "sRGB" => {
+            let intent = reader.u8()?;
+            let name = match intent {
+                0 => "perceptual",
+                1 => "relative colorimetric",
+                2 => "saturation",
+                3 => "absolute colorimetric",
+                _ => "unknown",
+            };
+            add("Rendering intent", format!("{} ({})", intent, name));
+        },
+        "pHYs" => {
+            add("Pixels per unit, X axis", reader.u32()?.to_string());
+            add("Pixels per unit, Y axis", reader.u32()?.to_string());
+            let unit = reader.u8()?;
+            add("Unit", format!("{} ({})", unit, if unit == 1 { "metre" } else { "unknown" }));
+        },
+        "tIME" => {
+            let year = reader.u16()?;
+            let (month, day, hour, minute, second) = (reader.u8()?, reader.u8()?, reader.u8()?, reader.u8()?, reader.u8()?);
+            add("Last modified", format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, hour, minute, second));
+        },
+        "tEXt" => {
+            add("Keyword", reader.latin1_until_nul()?);
+            add("Text", reader.latin1_rest());
+        },
+        "zTXt" => {
+            add("Keyword", reader.latin1_until_nul()?);
+            add("Compression method", reader.u8()?.to_string());
+            add("Compressed text", format!("{} bytes", data.len() - reader.position));
+        },
+        "iTXt" => {
+            add("Keyword", reader.latin1_until_nul()?);
+            let compressed = reader.u8()?;
+            add("Compression flag", compressed.to_string());
+            add("Compression method", reader.u8()?.to_string());
+            add("Language tag", reader.latin1_until_nul()?);
+            add("Translated keyword", String::from_utf8_lossy(reader.until_nul()?).into_owned());
+            if compressed == 0 {
+                add("Text", String::from_utf8_lossy(&data[reader.position..]).into_owned());
+            } else {
+                add("Compressed text", format!("{} bytes", data.len() - reader.position));
+            }
+        },
+        "IEND" if !data.is_empty() => return Err(Box::new(FieldError::Length(data.len()))),
+        _ => {},
+    }
+    Ok(fields)
+}
+
+// Reads big-endian values from the front of a chunk's data
+struct FieldReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> FieldReader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        if self.position + count > self.data.len() {
+            return Err(Box::new(FieldError::Length(self.data.len())));
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn until_nul(&mut self) -> Result<&'a [u8]> {
+        let rest = &self.data[self.position..];
+        match rest.iter().position(|&b| b == 0) {
+            Some(end) => {
+                self.position += end + 1;
+                Ok(&rest[..end])
+            },
+            None => Err(Box::new(FieldError::MissingSeparator)),
+        }
+    }
+
+    fn latin1_until_nul(&mut self) -> Result<String> {
+        Ok(self.until_nul()?.iter().map(|&b| b as char).collect())
+    }
+
+    fn latin1_rest(&mut self) -> String {
+        let text = self.data[self.position..].iter().map(|&b| b as char).collect();
+        self.position = self.data.len();
+        text
+    }
+}
+
+
+
+#[derive(Debug)]
+pub enum FieldError {
+    Length(usize),
+    MissingSeparator,
+}
+impl Display for FieldError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            FieldError::Length(n) => write!(f, "{} bytes is the wrong length for this chunk type.", n),
+            FieldError::MissingSeparator => write!(f, "A null separator is missing."),
+        }
+    }
+}
+impl std::error::Error for FieldError {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(b"0123456789abcdefXY\x00", 0x21);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "00000021  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|");
+        assert!(lines[1].starts_with("00000031  58 59 00 "));
+        assert!(lines[1].ends_with("|XY.|"));
+    }
+
+    #[test]
+    fn test_text_fields() {
+        let fields = fields(&chunk("tEXt", b"Author\0Ferris")).unwrap();
+        assert_eq!(fields[0], (String::from("Keyword"), String::from("Author")));
+        assert_eq!(fields[1], (String::from("Text"), String::from("Ferris")));
+    }
+
+    #[test]
+    fn test_physical_fields() {
+        let fields = fields(&chunk("pHYs", &[0, 0, 14, 195, 0, 0, 14, 195, 1])).unwrap();
+        assert_eq!(fields[0].1, "3779");
+        assert_eq!(fields[2].1, "1 (metre)");
+    }
+
+    #[test]
+    fn test_malformed_fields() {
+        assert!(fields(&chunk("gAMA", &[0, 1])).is_err());
+        assert!(fields(&chunk("tEXt", b"no separator")).is_err());
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        assert!(fields(&chunk("ruSt", b"secret")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_inspection_display() {
+        let raw = RawChunk { offset: 33, chunk: chunk("ruSt", b"secret"), stored_crc: 0 };
+        let output = ChunkInspection { index: 1, raw: &raw }.to_string();
+        assert!(output.starts_with("Chunk 1 (ruSt) at offset 33\n  Not a registered chunk type"));
+        assert!(output.contains("BAD, expected"));
+        assert!(output.contains("    00000029  73 65 63 72 65 74"));
+    }
+}
@@ -1,23 +1,40 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+
+mod apng;
 mod args;
+mod batch;
 mod chunk;
 mod chunk_type;
+mod colour;
 mod commands;
+mod diff;
+mod exif;
+mod explain;
+mod image;
+mod inspect;
+mod metadata;
+mod palette;
+mod palette_stego;
 mod png;
+mod registry;
+mod report;
+mod select;
+mod steganalysis;
+mod strip;
+mod transplant;
 
 pub type Error = Box<dyn std::error::Error>;
 pub type Result<T> = std::result::Result<T, Error>;
 
-fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    match args::process_args(&args) {
-        Ok(command_args) => {
-            match commands::process_command(command_args) {
-                Ok(_) => return Ok(()),
-                Err(e) => println!("{}", e),
-            };
+fn main() -> ExitCode {
+    let cli = args::Cli::parse();
+    match commands::process_command(cli.command) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(e.exit_code())
         },
-        Err(e) => println!("{}", e),
     }
-
-    Ok(())
 }
@@ -0,0 +1,212 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::{Error, Result};
+
+const INCHES_PER_METRE: f64 = 1.0 / 0.0254;
+
+/// The time a tIME chunk says the image was last changed, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModificationTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl ModificationTime {
+    pub const CHUNK_TYPE: &'static str = "tIME";
+
+    pub fn now() -> Self {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        ModificationTime::from_unix_seconds(seconds)
+    }
+
+    pub fn from_unix_seconds(seconds: u64) -> Self {
+        // Days since 1970 to a proleptic Gregorian date, counting years from March
+        // so that the leap day falls at the end
+        let days = (seconds / 86400) as i64 + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        let time_of_day = seconds % 86400;
+        ModificationTime {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (time_of_day / 3600) as u8,
+            minute: (time_of_day / 60 % 60) as u8,
+            second: (time_of_day % 60) as u8,
+        }
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        let mut data = self.year.to_be_bytes().to_vec();
+        data.extend([self.month, self.day, self.hour, self.minute, self.second]);
+        Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE).unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for ModificationTime {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        if data.len() != 7 {
+            return Err(Box::new(MetadataError::Length(Self::CHUNK_TYPE, data.len())));
+        }
+        let time = ModificationTime {
+            year: u16::from_be_bytes([data[0], data[1]]),
+            month: data[2],
+            day: data[3],
+            hour: data[4],
+            minute: data[5],
+            second: data[6],
+        };
+        // A second of 60 allows for leap seconds
+        if !(1..=12).contains(&time.month) || !(1..=31).contains(&time.day) || time.hour > 23 || time.minute > 59 || time.second > 60 {
+            return Err(Box::new(MetadataError::InvalidTime(time.to_string())));
+        }
+        Ok(time)
+    }
+}
+
+impl Display for ModificationTime {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Only the aspect ratio is known
+    Unknown,
+    Metre,
+}
+
+/// Pixels per unit in each direction, from a pHYs chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalDimensions {
+    pub x: u32,
+    pub y: u32,
+    pub unit: Unit,
+}
+
+impl PhysicalDimensions {
+    pub const CHUNK_TYPE: &'static str = "pHYs";
+
+    /// Dots per inch in each direction, when the unit is the metre.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            Unit::Metre => Some((self.x as f64 / INCHES_PER_METRE, self.y as f64 / INCHES_PER_METRE)),
+            Unit::Unknown => None,
+        }
+    }
+}
+
+impl TryFrom<&Chunk> for PhysicalDimensions {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        if data.len() != 9 {
+            return Err(Box::new(MetadataError::Length(Self::CHUNK_TYPE, data.len())));
+        }
+        let unit = match data[8] {
+            0 => Unit::Unknown,
+            1 => Unit::Metre,
+            u => return Err(Box::new(MetadataError::InvalidUnit(u))),
+        };
+        Ok(PhysicalDimensions {
+            x: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            y: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            unit,
+        })
+    }
+}
+
+impl Display for PhysicalDimensions {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self.dpi() {
+            Some((x, _)) if self.x == self.y => write!(f, "{} pixels per metre ({:.0} DPI)", self.x, x),
+            Some((x, y)) => write!(f, "{} x {} pixels per metre ({:.0} x {:.0} DPI)", self.x, self.y, x, y),
+            None => write!(f, "pixel aspect ratio {}:{}, no physical size", self.x, self.y),
+        }
+    }
+}
+
+
+
+#[derive(Debug)]
+pub enum MetadataError {
+    Length(&'static str, usize),
+    InvalidTime(String),
+    InvalidUnit(u8),
+}
+impl Display for MetadataError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            MetadataError::Length(chunk_type, n) => write!(f, "{} bytes is the wrong length for a {} chunk.", n, chunk_type),
+            MetadataError::InvalidTime(t) => write!(f, "{} is not a valid tIME.", t),
+            MetadataError::InvalidUnit(u) => write!(f, "{} is not a valid pHYs unit.", u),
+        }
+    }
+}
+impl std::error::Error for MetadataError {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_from_unix_seconds() {
+        assert_eq!(ModificationTime::from_unix_seconds(0).to_string(), "1970-01-01 00:00:00 UTC");
+        assert_eq!(ModificationTime::from_unix_seconds(951782400).to_string(), "2000-02-29 00:00:00 UTC");
+        assert_eq!(ModificationTime::from_unix_seconds(1700000000).to_string(), "2023-11-14 22:13:20 UTC");
+        assert_eq!(ModificationTime::from_unix_seconds(4107542399).to_string(), "2100-02-28 23:59:59 UTC");
+    }
+
+    #[test]
+    fn test_time_round_trip() {
+        let time = ModificationTime::from_unix_seconds(1700000000);
+        let written = time.as_chunk();
+        assert_eq!(written.data(), &[7, 231, 11, 14, 22, 13, 20]);
+        assert_eq!(ModificationTime::try_from(&written).unwrap(), time);
+    }
+
+    #[test]
+    fn test_invalid_time() {
+        assert!(ModificationTime::try_from(&chunk("tIME", &[7, 231, 13, 1, 0, 0, 0])).is_err());
+        assert!(ModificationTime::try_from(&chunk("tIME", &[7, 231, 12, 31, 23, 59, 60])).is_ok());
+        assert!(ModificationTime::try_from(&chunk("tIME", &[7, 231, 1])).is_err());
+    }
+
+    #[test]
+    fn test_physical_dimensions() {
+        let metres = PhysicalDimensions::try_from(&chunk("pHYs", &[0, 0, 14, 195, 0, 0, 14, 195, 1])).unwrap();
+        assert_eq!(metres, PhysicalDimensions { x: 3779, y: 3779, unit: Unit::Metre });
+        assert_eq!(metres.to_string(), "3779 pixels per metre (96 DPI)");
+
+        let uneven = PhysicalDimensions { x: 2835, y: 5669, unit: Unit::Metre };
+        assert_eq!(uneven.to_string(), "2835 x 5669 pixels per metre (72 x 144 DPI)");
+
+        let ratio = PhysicalDimensions::try_from(&chunk("pHYs", &[0, 0, 0, 1, 0, 0, 0, 2, 0])).unwrap();
+        assert!(ratio.dpi().is_none());
+        assert!(PhysicalDimensions::try_from(&chunk("pHYs", &[0, 0, 0, 1, 0, 0, 0, 2, 2])).is_err());
+    }
+}
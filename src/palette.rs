@@ -0,0 +1,362 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::image::{ColourType, ImageHeader};
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// The colours of a PLTE chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    entries: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub const CHUNK_TYPE: &'static str = "PLTE";
+
+    /// The file's palette, if it has one.
+    pub fn from_png(png: &Png) -> Result<Option<Self>> {
+        match png.chunk_by_type(Self::CHUNK_TYPE) {
+            Some(chunk) => Ok(Some(Palette::try_from(chunk)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn entries(&self) -> &[[u8; 3]] {
+        &self.entries
+    }
+
+    pub fn set_entry(&mut self, index: usize, rgb: [u8; 3]) -> Result<()> {
+        match self.entries.get_mut(index) {
+            Some(entry) => {
+                *entry = rgb;
+                Ok(())
+            },
+            None => Err(Box::new(PaletteError::Index(index))),
+        }
+    }
+
+    /// Checks the palette is allowed for the image's colour type and, for
+    /// indexed images, that every entry can be referred to at its bit depth.
+    pub fn validate(&self, header: &ImageHeader) -> Result<()> {
+        match header.colour_type {
+            ColourType::Greyscale | ColourType::GreyscaleAlpha => Err(Box::new(PaletteError::NotAllowed(Self::CHUNK_TYPE, header.colour_type))),
+            ColourType::Indexed if self.entries.len() > 1 << header.bit_depth => {
+                Err(Box::new(PaletteError::TooManyEntries(self.entries.len(), 1 << header.bit_depth)))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE).unwrap(), self.entries.concat())
+    }
+}
+
+impl TryFrom<&Chunk> for Palette {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        if !data.len().is_multiple_of(3) {
+            return Err(Box::new(PaletteError::Length(Self::CHUNK_TYPE, data.len())));
+        }
+        if data.is_empty() || data.len() > 256 * 3 {
+            return Err(Box::new(PaletteError::EntryCount(data.len() / 3)));
+        }
+        Ok(Palette { entries: data.chunks(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect() })
+    }
+}
+
+/// A tRNS chunk, whose layout depends on the colour type: an alpha value per
+/// palette entry, or a single greyscale or RGB colour that is fully
+/// transparent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    Palette(Vec<u8>),
+    Greyscale(u16),
+    Truecolour([u16; 3]),
+}
+
+impl Transparency {
+    pub const CHUNK_TYPE: &'static str = "tRNS";
+
+    pub fn from_png(png: &Png, header: &ImageHeader) -> Result<Option<Self>> {
+        match png.chunk_by_type(Self::CHUNK_TYPE) {
+            Some(chunk) => Ok(Some(Transparency::parse(chunk, header)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn parse(chunk: &Chunk, header: &ImageHeader) -> Result<Self> {
+        let data = chunk.data();
+        let sample = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        match header.colour_type {
+            ColourType::Indexed => Ok(Transparency::Palette(data.to_vec())),
+            ColourType::Greyscale if data.len() == 2 => Ok(Transparency::Greyscale(sample(0))),
+            ColourType::Truecolour if data.len() == 6 => Ok(Transparency::Truecolour([sample(0), sample(2), sample(4)])),
+            ColourType::Greyscale | ColourType::Truecolour => Err(Box::new(PaletteError::Length(Self::CHUNK_TYPE, data.len()))),
+            colour_type => Err(Box::new(PaletteError::NotAllowed(Self::CHUNK_TYPE, colour_type))),
+        }
+    }
+
+    /// Checks the chunk suits the image: no more alpha values than palette
+    /// entries, or a colour that fits the bit depth.
+    pub fn validate(&self, header: &ImageHeader, palette: Option<&Palette>) -> Result<()> {
+        let limit = (1u32 << header.bit_depth) - 1;
+        match (self, header.colour_type) {
+            (Transparency::Palette(alphas), ColourType::Indexed) => match palette {
+                Some(p) if alphas.len() > p.entries().len() => Err(Box::new(PaletteError::TooManyEntries(alphas.len(), p.entries().len()))),
+                Some(_) => Ok(()),
+                None => Err(Box::new(PaletteError::MissingPalette)),
+            },
+            (Transparency::Greyscale(grey), ColourType::Greyscale) if *grey as u32 > limit => Err(Box::new(PaletteError::SampleRange(*grey))),
+            (Transparency::Greyscale(_), ColourType::Greyscale) => Ok(()),
+            (Transparency::Truecolour(rgb), ColourType::Truecolour) => match rgb.iter().find(|&&s| s as u32 > limit) {
+                Some(sample) => Err(Box::new(PaletteError::SampleRange(*sample))),
+                None => Ok(()),
+            },
+            (_, colour_type) => Err(Box::new(PaletteError::NotAllowed(Self::CHUNK_TYPE, colour_type))),
+        }
+    }
+
+    /// The alpha of a palette entry; entries past the end of the chunk are opaque.
+    pub fn alpha(&self, index: usize) -> u8 {
+        match self {
+            Transparency::Palette(alphas) => alphas.get(index).copied().unwrap_or(255),
+            _ => 255,
+        }
+    }
+
+    pub fn set_alpha(&mut self, index: usize, alpha: u8) -> Result<()> {
+        match self {
+            Transparency::Palette(alphas) => {
+                if alphas.len() <= index {
+                    alphas.resize(index + 1, 255);
+                }
+                alphas[index] = alpha;
+                Ok(())
+            },
+            _ => Err(Box::new(PaletteError::AlphaWithoutPalette)),
+        }
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        let data = match self {
+            Transparency::Palette(alphas) => {
+                // Trailing opaque entries are implied
+                let end = alphas.iter().rposition(|&a| a != 255).map_or(0, |i| i + 1);
+                alphas[..end].to_vec()
+            },
+            Transparency::Greyscale(grey) => grey.to_be_bytes().to_vec(),
+            Transparency::Truecolour(rgb) => rgb.iter().flat_map(|s| s.to_be_bytes()).collect(),
+        };
+        Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE).unwrap(), data)
+    }
+}
+
+/// Writes a palette and its transparency back to a file that already has a
+/// PLTE chunk. The tRNS chunk is added, replaced, or removed when every entry
+/// is opaque; without a transparency it is left alone.
+pub fn store(png: &mut Png, palette: &Palette, transparency: Option<&Transparency>) -> Result<()> {
+    let index_of = |png: &Png, chunk_type: &str| png.chunks().iter().position(|c| c.chunk_type().to_string() == chunk_type);
+    let palette_index = match index_of(png, Palette::CHUNK_TYPE) {
+        Some(i) => i,
+        None => return Err(Box::new(PaletteError::MissingPalette)),
+    };
+    png.replace_chunk_data(palette_index, palette.as_chunk().data().to_vec())?;
+    let transparency_chunk = match transparency {
+        Some(t) => t.as_chunk(),
+        None => return Ok(()),
+    };
+    match (index_of(png, Transparency::CHUNK_TYPE), transparency_chunk.data().is_empty()) {
+        (Some(i), true) => png.remove_chunk_at(i).map(|_| ()),
+        (Some(i), false) => png.replace_chunk_data(i, transparency_chunk.data().to_vec()).map(|_| ()),
+        (None, true) => Ok(()),
+        (None, false) => png.insert_chunk(palette_index + 1, transparency_chunk),
+    }
+}
+
+/// One palette entry as `print` and `palette` show it.
+#[derive(Serialize)]
+pub struct PaletteEntry {
+    pub index: usize,
+    pub colour: String,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+pub fn palette_entries(palette: &Palette, transparency: Option<&Transparency>) -> Vec<PaletteEntry> {
+    palette
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(index, &[red, green, blue])| PaletteEntry {
+            index,
+            colour: format!("#{:02x}{:02x}{:02x}", red, green, blue),
+            red,
+            green,
+            blue,
+            alpha: transparency.map_or(255, |t| t.alpha(index)),
+        })
+        .collect()
+}
+
+/// Problems with a file's PLTE and tRNS chunks, judged against its IHDR.
+pub fn palette_problems(png: &Png) -> Vec<String> {
+    let header = match ImageHeader::from_png(png) {
+        Ok(h) => h,
+        Err(e) => return vec![e.to_string()],
+    };
+    let mut problems = vec![];
+    let palette = match Palette::from_png(png) {
+        Ok(p) => p,
+        Err(e) => {
+            problems.push(e.to_string());
+            None
+        },
+    };
+    match &palette {
+        Some(p) => problems.extend(p.validate(&header).err().map(|e| e.to_string())),
+        None if header.colour_type == ColourType::Indexed => problems.push(PaletteError::MissingPalette.to_string()),
+        None => {},
+    }
+    match Transparency::from_png(png, &header) {
+        Ok(Some(t)) => problems.extend(t.validate(&header, palette.as_ref()).err().map(|e| e.to_string())),
+        Ok(None) => {},
+        Err(e) => problems.push(e.to_string()),
+    }
+    problems
+}
+
+
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Length(&'static str, usize),
+    EntryCount(usize),
+    NotAllowed(&'static str, ColourType),
+    TooManyEntries(usize, usize),
+    MissingPalette,
+    SampleRange(u16),
+    Index(usize),
+    AlphaWithoutPalette,
+}
+impl Display for PaletteError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PaletteError::Length(chunk_type, n) => write!(f, "{} bytes is the wrong length for a {} chunk.", n, chunk_type),
+            PaletteError::EntryCount(n) => write!(f, "A palette must have between 1 and 256 entries, not {}.", n),
+            PaletteError::NotAllowed(chunk_type, colour_type) => write!(f, "{} is not allowed in {} images.", chunk_type, colour_type),
+            PaletteError::TooManyEntries(n, limit) => write!(f, "{} entries is more than the {} this image can use.", n, limit),
+            PaletteError::MissingPalette => write!(f, "An indexed-colour image needs a PLTE chunk."),
+            PaletteError::SampleRange(s) => write!(f, "{} does not fit the image's bit depth.", s),
+            PaletteError::Index(i) => write!(f, "There is no palette entry {}.", i),
+            PaletteError::AlphaWithoutPalette => write!(f, "Only indexed-colour images have an alpha value for each palette entry."),
+        }
+    }
+}
+impl std::error::Error for PaletteError {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(colour_type: ColourType, bit_depth: u8) -> ImageHeader {
+        ImageHeader { width: 1, height: 1, bit_depth, colour_type, interlaced: false }
+    }
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn palette(entries: usize) -> Palette {
+        Palette::try_from(&chunk("PLTE", &vec![7; entries * 3])).unwrap()
+    }
+
+    #[test]
+    fn test_palette_round_trip() {
+        let mut palette = Palette::try_from(&chunk("PLTE", &[1, 2, 3, 4, 5, 6])).unwrap();
+        assert_eq!(palette.entries(), &[[1, 2, 3], [4, 5, 6]]);
+        palette.set_entry(1, [9, 9, 9]).unwrap();
+        assert!(palette.set_entry(2, [0, 0, 0]).is_err());
+        assert_eq!(palette.as_chunk().data(), &[1, 2, 3, 9, 9, 9]);
+    }
+
+    #[test]
+    fn test_invalid_palettes() {
+        assert!(Palette::try_from(&chunk("PLTE", &[1, 2])).is_err());
+        assert!(Palette::try_from(&chunk("PLTE", &[])).is_err());
+        assert!(Palette::try_from(&chunk("PLTE", &[0; 257 * 3])).is_err());
+    }
+
+    #[test]
+    fn test_palette_against_header() {
+        assert!(palette(4).validate(&header(ColourType::Indexed, 2)).is_ok());
+        assert!(palette(5).validate(&header(ColourType::Indexed, 2)).is_err());
+        assert!(palette(5).validate(&header(ColourType::Truecolour, 8)).is_ok());
+        assert!(palette(1).validate(&header(ColourType::Greyscale, 8)).is_err());
+    }
+
+    #[test]
+    fn test_transparency_layouts() {
+        let grey = Transparency::parse(&chunk("tRNS", &[0, 3]), &header(ColourType::Greyscale, 2)).unwrap();
+        assert_eq!(grey, Transparency::Greyscale(3));
+        assert!(grey.validate(&header(ColourType::Greyscale, 2), None).is_ok());
+        assert!(Transparency::Greyscale(4).validate(&header(ColourType::Greyscale, 2), None).is_err());
+
+        let rgb = Transparency::parse(&chunk("tRNS", &[0, 1, 0, 2, 0, 3]), &header(ColourType::Truecolour, 8)).unwrap();
+        assert_eq!(rgb.as_chunk().data(), &[0, 1, 0, 2, 0, 3]);
+        assert!(Transparency::parse(&chunk("tRNS", &[0, 1]), &header(ColourType::TruecolourAlpha, 8)).is_err());
+    }
+
+    #[test]
+    fn test_palette_alpha() {
+        let indexed = header(ColourType::Indexed, 8);
+        let mut alphas = Transparency::parse(&chunk("tRNS", &[0, 128]), &indexed).unwrap();
+        assert_eq!(alphas.alpha(1), 128);
+        assert_eq!(alphas.alpha(5), 255);
+        assert!(alphas.validate(&indexed, Some(&palette(2))).is_ok());
+        assert!(alphas.validate(&indexed, Some(&palette(1))).is_err());
+        assert!(alphas.validate(&indexed, None).is_err());
+
+        alphas.set_alpha(1, 255).unwrap();
+        alphas.set_alpha(3, 255).unwrap();
+        assert_eq!(alphas.as_chunk().data(), &[0]);
+        assert!(Transparency::Truecolour([0; 3]).set_alpha(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_store() {
+        let chunks = vec![header(ColourType::Truecolour, 8).as_chunk(), palette(2).as_chunk(), chunk("IDAT", &[]), chunk("IEND", &[])];
+        let mut png = Png::from_chunks(chunks);
+        let mut edited = palette(2);
+        edited.set_entry(0, [1, 2, 3]).unwrap();
+        store(&mut png, &edited, None).unwrap();
+        assert_eq!(png.chunks()[1].data(), &[1, 2, 3, 7, 7, 7]);
+        assert!(png.chunk_by_type("tRNS").is_none());
+
+        let mut indexed = Png::from_chunks(vec![header(ColourType::Indexed, 8).as_chunk(), palette(2).as_chunk(), chunk("IDAT", &[]), chunk("IEND", &[])]);
+        store(&mut indexed, &edited, Some(&Transparency::Palette(vec![128]))).unwrap();
+        assert_eq!(indexed.chunks()[2].data(), &[128]);
+    }
+
+    #[test]
+    fn test_palette_problems() {
+        let mut png = Png::from_chunks(vec![header(ColourType::Indexed, 1).as_chunk(), chunk("IEND", &[])]);
+        assert_eq!(palette_problems(&png), vec![PaletteError::MissingPalette.to_string()]);
+        png.insert_chunk(1, palette(3).as_chunk()).unwrap();
+        assert_eq!(palette_problems(&png).len(), 1);
+        // Edits refuse to put tRNS before PLTE, so build the broken file directly
+        let mut chunks = png.chunks().to_vec();
+        chunks.insert(1, chunk("tRNS", &[0, 0, 0, 0]));
+        assert_eq!(palette_problems(&Png::from_chunks(chunks)).len(), 2);
+    }
+}
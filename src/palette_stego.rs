@@ -0,0 +1,402 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::apng;
+use crate::chunk_type::ChunkType;
+use crate::image::{ColourType, Image, ImageHeader};
+use crate::palette::{self, Palette, PaletteError, Transparency};
+use crate::png::Png;
+use crate::Result;
+
+// A message is hidden in the order of an indexed image's distinct colours. The
+// pixels are renumbered to follow their colours, so the image looks exactly the
+// same; only the palette order, and the index bytes, change. A palette with m
+// distinct colours can be arranged in m! orders, which carry floor(log2(m!))
+// bits. Entries that share a colour and alpha are kept together, so
+// duplicating an entry does not add capacity but does not lose any either.
+//
+// The payload is a length byte, the message and zero padding, read as one big
+// number and written out as a permutation of the sorted colours.
+
+/// How many message bytes a palette can hide.
+pub fn capacity(palette: &Palette, transparency: Option<&Transparency>) -> usize {
+    payload_width(distinct_colours(&colour_keys(palette, transparency)).len()).saturating_sub(1)
+}
+
+/// Reorders the palette of an indexed image so that it carries `message`,
+/// renumbering the pixels, tRNS, bKGD and hIST to match. Animated images are
+/// refused, as the frames after the first would keep their old numbering.
+pub fn embed(png: &mut Png, message: &[u8]) -> Result<()> {
+    if apng::is_animated(png) {
+        return Err(Box::new(PaletteStegoError::Animated));
+    }
+    let (header, palette, transparency) = read_indexed(png)?;
+    let keys = colour_keys(&palette, transparency.as_ref());
+    let mut remaining = distinct_colours(&keys);
+    let width = payload_width(remaining.len());
+    if message.len() >= width {
+        return Err(Box::new(PaletteStegoError::TooLong(message.len(), width.saturating_sub(1))));
+    }
+
+    let mut payload = vec![message.len() as u8];
+    payload.extend_from_slice(message);
+    payload.resize(width, 0);
+    let mut number = Natural::from_be_bytes(&payload);
+    let mut order = vec![];
+    while !remaining.is_empty() {
+        let digit = number.div_rem(remaining.len() as u32) as usize;
+        order.push(remaining.remove(digit));
+    }
+
+    // Entries follow their colour, and entries of one colour keep their relative order
+    let mut new_index = vec![0; keys.len()];
+    let mut next = 0;
+    for colour in order.iter() {
+        for (old, _) in keys.iter().enumerate().filter(|(_, key)| *key == colour) {
+            new_index[old] = next;
+            next += 1;
+        }
+    }
+    renumber(png, &header, &palette, transparency.as_ref(), &new_index)
+}
+
+/// Reads a message hidden by `embed`.
+pub fn extract(png: &Png) -> Result<Vec<u8>> {
+    let (_, palette, transparency) = read_indexed(png)?;
+    let keys = colour_keys(&palette, transparency.as_ref());
+    let mut remaining = distinct_colours(&keys);
+    let count = remaining.len();
+    let width = payload_width(count);
+    if width == 0 {
+        return Err(Box::new(PaletteStegoError::NoMessage));
+    }
+
+    let mut digits = vec![];
+    for key in keys.iter() {
+        if let Some(digit) = remaining.iter().position(|colour| colour == key) {
+            remaining.remove(digit);
+            digits.push(digit as u32);
+        }
+    }
+    let mut number = Natural::new(0);
+    for (i, digit) in digits.iter().enumerate().rev() {
+        number.mul_add((count - i) as u32, *digit);
+    }
+
+    // A palette nobody embedded in decodes to noise, which almost never has
+    // a length that fits and zero padding after it
+    let payload = number.take_be_bytes(width);
+    let length = payload[0] as usize;
+    if !number.is_zero() || length >= width || payload[1 + length..].iter().any(|&b| b != 0) {
+        return Err(Box::new(PaletteStegoError::NoMessage));
+    }
+    Ok(payload[1..1 + length].to_vec())
+}
+
+fn read_indexed(png: &Png) -> Result<(ImageHeader, Palette, Option<Transparency>)> {
+    let header = ImageHeader::from_png(png)?;
+    if header.colour_type != ColourType::Indexed {
+        return Err(Box::new(PaletteStegoError::NotIndexed(header.colour_type)));
+    }
+    let palette = match Palette::from_png(png)? {
+        Some(p) => p,
+        None => return Err(Box::new(PaletteError::MissingPalette)),
+    };
+    let transparency = Transparency::from_png(png, &header)?;
+    Ok((header, palette, transparency))
+}
+
+// Each entry's colour and alpha, which is what a viewer shows for it
+fn colour_keys(palette: &Palette, transparency: Option<&Transparency>) -> Vec<[u8; 4]> {
+    palette
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(i, &[red, green, blue])| [red, green, blue, transparency.map_or(255, |t| t.alpha(i))])
+        .collect()
+}
+
+fn distinct_colours(keys: &[[u8; 4]]) -> Vec<[u8; 4]> {
+    let mut colours = keys.to_vec();
+    colours.sort();
+    colours.dedup();
+    colours
+}
+
+// Whole bytes in floor(log2(colours!)) bits
+fn payload_width(colours: usize) -> usize {
+    let mut factorial = Natural::new(1);
+    for n in 2..=colours {
+        factorial.mul_add(n as u32, 0);
+    }
+    (factorial.bits() - 1) / 8
+}
+
+// Moves palette entry `i` to `new_index[i]` and updates everything that refers
+// to entries by index
+fn renumber(png: &mut Png, header: &ImageHeader, palette: &Palette, transparency: Option<&Transparency>, new_index: &[usize]) -> Result<()> {
+    let image = Image::decode(png)?;
+    let samples = image
+        .samples()
+        .iter()
+        .map(|&s| new_index.get(s as usize).map_or(s, |&n| n as u16))
+        .collect();
+    Image::new(header.width, header.height, header.bit_depth, ColourType::Indexed, samples)?.store(png)?;
+
+    let mut reordered = palette.clone();
+    let mut alphas = Transparency::Palette(vec![]);
+    for (old, &new) in new_index.iter().enumerate() {
+        reordered.set_entry(new, palette.entries()[old])?;
+        alphas.set_alpha(new, transparency.map_or(255, |t| t.alpha(old)))?;
+    }
+    palette::store(png, &reordered, Some(&alphas))?;
+
+    let position = |png: &Png, chunk_type: &str| png.chunks().iter().position(|c| c.chunk_type() == &ChunkType::from_str(chunk_type).unwrap());
+    if let Some(i) = position(png, "bKGD") {
+        if let [index] = png.chunks()[i].data() {
+            let index = new_index.get(*index as usize).map_or(*index, |&n| n as u8);
+            png.replace_chunk_data(i, vec![index])?;
+        }
+    }
+    if let Some(i) = position(png, "hIST") {
+        let data = png.chunks()[i].data();
+        if data.len() == new_index.len() * 2 {
+            let mut histogram = vec![0; data.len()];
+            for (old, &new) in new_index.iter().enumerate() {
+                histogram[new * 2..new * 2 + 2].copy_from_slice(&data[old * 2..old * 2 + 2]);
+            }
+            png.replace_chunk_data(i, histogram)?;
+        }
+    }
+    Ok(())
+}
+
+// An arbitrarily large unsigned integer, as little-endian 32-bit limbs. The
+// largest needed is 256!, about 1700 bits.
+struct Natural(Vec<u32>);
+
+impl Natural {
+    fn new(value: u32) -> Self {
+        Natural(vec![value])
+    }
+
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        let mut number = Natural::new(0);
+        for &byte in bytes {
+            number.mul_add(256, byte as u32);
+        }
+        number
+    }
+
+    // Divides by 256 `width` times, leaving whatever did not fit
+    fn take_be_bytes(&mut self, width: usize) -> Vec<u8> {
+        let mut bytes: Vec<u8> = (0..width).map(|_| self.div_rem(256) as u8).collect();
+        bytes.reverse();
+        bytes
+    }
+
+    fn mul_add(&mut self, factor: u32, addend: u32) {
+        let mut carry = addend as u64;
+        for limb in self.0.iter_mut() {
+            let value = *limb as u64 * factor as u64 + carry;
+            *limb = value as u32;
+            carry = value >> 32;
+        }
+        if carry > 0 {
+            self.0.push(carry as u32);
+        }
+    }
+
+    fn div_rem(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+        for limb in self.0.iter_mut().rev() {
+            let value = (remainder << 32) | *limb as u64;
+            *limb = (value / divisor as u64) as u32;
+            remainder = value % divisor as u64;
+        }
+        while self.0.len() > 1 && self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+        remainder as u32
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|&limb| limb == 0)
+    }
+
+    fn bits(&self) -> usize {
+        let top = self.0[self.0.len() - 1];
+        (self.0.len() - 1) * 32 + (32 - top.leading_zeros() as usize)
+    }
+}
+
+
+
+#[derive(Debug)]
+pub enum PaletteStegoError {
+    NotIndexed(ColourType),
+    TooLong(usize, usize),
+    NoMessage,
+    Animated,
+}
+impl Display for PaletteStegoError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PaletteStegoError::NotIndexed(colour_type) => write!(f, "Only indexed-colour images can hide a message in their palette, not {} images.", colour_type),
+            PaletteStegoError::TooLong(n, capacity) => write!(f, "The message is {} bytes, but this palette can only hide {}.", n, capacity),
+            PaletteStegoError::NoMessage => write!(f, "The palette order does not hold a message."),
+            PaletteStegoError::Animated => write!(f, "Animated images cannot hide a message in their palette."),
+        }
+    }
+}
+impl std::error::Error for PaletteStegoError {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    // A 16x4 image using every entry of a palette of `colours`, with the
+    // given alphas and a background of entry 1
+    fn indexed_png(colours: &[[u8; 3]], alphas: &[u8]) -> Png {
+        let samples = (0..64).map(|i| (i * 7 % colours.len()) as u16).collect();
+        let mut png = Image::new(16, 4, 8, ColourType::Indexed, samples).unwrap().to_png();
+        png.insert_chunk(1, chunk("PLTE", &colours.concat())).unwrap();
+        let mut next = 2;
+        if !alphas.is_empty() {
+            png.insert_chunk(next, chunk("tRNS", alphas)).unwrap();
+            next += 1;
+        }
+        png.insert_chunk(next, chunk("bKGD", &[1])).unwrap();
+        png
+    }
+
+    fn testing_palette(count: usize) -> Vec<[u8; 3]> {
+        (0..count).map(|i| [i as u8, (i * 8 % 256) as u8, 0]).collect()
+    }
+
+    // What each pixel looks like, plus the background colour
+    fn rendered(png: &Png) -> (Vec<[u8; 4]>, [u8; 4]) {
+        let header = ImageHeader::from_png(png).unwrap();
+        let palette = Palette::from_png(png).unwrap().unwrap();
+        let transparency = Transparency::from_png(png, &header).unwrap();
+        let keys = colour_keys(&palette, transparency.as_ref());
+        let pixels = Image::decode(png).unwrap().samples().iter().map(|&s| keys[s as usize]).collect();
+        let background = keys[png.chunk_by_type("bKGD").unwrap().data()[0] as usize];
+        (pixels, background)
+    }
+
+    #[test]
+    fn test_capacity() {
+        let capacity_of = |count: usize| capacity(&Palette::try_from(&chunk("PLTE", &testing_palette(count).concat())).unwrap(), None);
+        // 5! = 120 holds 6 bits, 6! = 720 holds 9
+        assert_eq!(capacity_of(5), 0);
+        assert_eq!(capacity_of(6), 0);
+        assert_eq!(capacity_of(16), 4);
+        // log2(256!) is about 1684
+        assert_eq!(capacity_of(256), 209);
+    }
+
+    #[test]
+    fn test_duplicates_do_not_add_capacity() {
+        let mut colours = testing_palette(16);
+        colours.extend(testing_palette(4));
+        let palette = Palette::try_from(&chunk("PLTE", &colours.concat())).unwrap();
+        assert_eq!(capacity(&palette, None), 4);
+        // The same colour with a different alpha looks different
+        let transparency = Transparency::Palette(vec![255; 16].into_iter().chain([0, 0, 0, 0]).collect());
+        assert_eq!(capacity(&palette, Some(&transparency)), 6);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut png = indexed_png(&testing_palette(32), &[]);
+        let before = rendered(&png);
+        embed(&mut png, b"hidden!").unwrap();
+        assert_eq!(extract(&png).unwrap(), b"hidden!");
+        assert_eq!(rendered(&png), before);
+        assert!(png.chunk_by_type("tRNS").is_none());
+    }
+
+    #[test]
+    fn test_round_trip_with_transparency_and_duplicates() {
+        let mut colours = testing_palette(24);
+        colours.extend(testing_palette(6));
+        let alphas: Vec<u8> = (0..30).map(|i| if i % 5 == 0 { 0 } else { 255 }).collect();
+        let mut png = indexed_png(&colours, &alphas);
+        let before = rendered(&png);
+        let message = vec![0xa5; capacity(&Palette::from_png(&png).unwrap().unwrap(), Some(&Transparency::Palette(alphas)))];
+        embed(&mut png, &message).unwrap();
+        assert_eq!(extract(&png).unwrap(), message);
+        assert_eq!(rendered(&png), before);
+    }
+
+    #[test]
+    fn test_histogram_follows_entries() {
+        let mut png = indexed_png(&testing_palette(16), &[]);
+        let histogram: Vec<u8> = (0..16u16).flat_map(|i| (i * 100).to_be_bytes()).collect();
+        png.insert_chunk(3, chunk("hIST", &histogram)).unwrap();
+        embed(&mut png, b"ab").unwrap();
+
+        let palette = Palette::from_png(&png).unwrap().unwrap();
+        let data = png.chunk_by_type("hIST").unwrap().data();
+        for (i, entry) in palette.entries().iter().enumerate() {
+            let original = entry[0] as u16;
+            assert_eq!(u16::from_be_bytes([data[i * 2], data[i * 2 + 1]]), original * 100);
+        }
+    }
+
+    #[test]
+    fn test_empty_message() {
+        let mut png = indexed_png(&testing_palette(8), &[]);
+        embed(&mut png, b"").unwrap();
+        assert_eq!(extract(&png).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_message_too_long() {
+        let mut png = indexed_png(&testing_palette(16), &[]);
+        assert!(embed(&mut png, b"12345").is_err());
+        assert!(embed(&mut png, b"1234").is_ok());
+    }
+
+    #[test]
+    fn test_untouched_palette_has_no_message() {
+        let mut colours = testing_palette(64);
+        colours.reverse();
+        assert!(extract(&indexed_png(&colours, &[])).is_err());
+    }
+
+    #[test]
+    fn test_not_indexed() {
+        let mut png = Image::new(1, 1, 8, ColourType::Greyscale, vec![0]).unwrap().to_png();
+        assert!(embed(&mut png, b"").is_err());
+        assert!(extract(&png).is_err());
+    }
+
+    #[test]
+    fn test_animated() {
+        let mut png = indexed_png(&testing_palette(16), &[]);
+        let control = apng::AnimationControl { frames: 1, plays: 0 }.as_chunk();
+        png.insert_chunk(1, control).unwrap();
+        let before = png.as_bytes();
+        assert!(embed(&mut png, b"hi").is_err());
+        assert_eq!(png.as_bytes(), before);
+    }
+
+    #[test]
+    fn test_natural() {
+        let mut number = Natural::from_be_bytes(&[1, 0, 0, 0, 0, 0, 0, 0, 5]);
+        assert_eq!(number.bits(), 65);
+        assert_eq!(number.div_rem(10), 1);
+        let mut bytes = Natural::from_be_bytes(&[0, 3, 255, 7]);
+        assert_eq!(bytes.take_be_bytes(3), vec![3, 255, 7]);
+        assert!(bytes.is_zero());
+    }
+}
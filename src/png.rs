@@ -1,6 +1,10 @@
 use std::fmt::{Display, Formatter};
 
+use crate::apng;
 use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::registry::{self, Placement};
+use crate::select::ChunkSelector;
 use crate::{Error, Result};
 
 pub struct Png {
@@ -14,23 +18,123 @@ impl Png {
         Png { chunks }
     }
 
+    /// Adds a chunk at the end, or just before IEND if the file has one.
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+        let end = self.chunks.iter().rposition(|c| c.chunk_type().to_string() == "IEND").unwrap_or(self.chunks.len());
+        self.chunks.insert(end, chunk);
+        apng::renumber(&mut self.chunks);
     }
 
-    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
-        let (mut found, mut index) = (false, 0);
-        for (i, chunk) in self.chunks.iter_mut().enumerate() {
-            if chunk.chunk_type().to_string() == chunk_type {
-                found = true;
-                index = i;
-                break;
-            }
+    /// Inserts a chunk so that it ends up at `index`, which may be the number
+    /// of chunks to append it.
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        if index > self.chunks.len() {
+            return Err(Box::from(PngError::IndexOutOfRange(index)));
+        }
+        self.edit(|chunks| {
+            chunks.insert(index, chunk);
+            apng::renumber(chunks);
+        })
+    }
+
+    /// Removes the chunk at `index`, counting from the first chunk after the
+    /// signature.
+    pub fn remove_chunk_at(&mut self, index: usize) -> Result<Chunk> {
+        if index >= self.chunks.len() {
+            return Err(Box::from(PngError::IndexOutOfRange(index)));
+        }
+        let removed = self.chunks[index].clone();
+        self.edit(|chunks| {
+            chunks.remove(index);
+            apng::renumber(chunks);
+        })?;
+        Ok(removed)
+    }
+
+    /// Removes every chunk the selector matches, returning them in file order.
+    pub fn remove_selected(&mut self, selector: &ChunkSelector) -> Result<Vec<Chunk>> {
+        let indices: Vec<usize> = self.select(selector).map(|(index, _)| index).collect();
+        if indices.is_empty() {
+            return Err(Box::from(PngError::NoChunksSelected));
+        }
+        self.remove_chunks_at(&indices)
+    }
+
+    /// Removes the chunks at `indices` in a single edit, returning them in
+    /// file order.
+    pub fn remove_chunks_at(&mut self, indices: &[usize]) -> Result<Vec<Chunk>> {
+        if let Some(&index) = indices.iter().find(|&&i| i >= self.chunks.len()) {
+            return Err(Box::from(PngError::IndexOutOfRange(index)));
+        }
+        let removed: Vec<Chunk> = self.chunks.iter().enumerate().filter(|(i, _)| indices.contains(i)).map(|(_, c)| c.clone()).collect();
+        self.edit(|chunks| {
+            let mut index = 0;
+            chunks.retain(|_| {
+                index += 1;
+                !indices.contains(&(index - 1))
+            });
+            apng::renumber(chunks);
+        })?;
+        Ok(removed)
+    }
+
+    /// Swaps the data of the chunk at `index` for `data`, returning the old
+    /// chunk.
+    pub fn replace_chunk_data(&mut self, index: usize, data: Vec<u8>) -> Result<Chunk> {
+        let old = match self.chunks.get(index) {
+            Some(c) => c.clone(),
+            None => return Err(Box::from(PngError::IndexOutOfRange(index))),
+        };
+        self.edit(|chunks| chunks[index] = Chunk::new(old.chunk_type().clone(), data))?;
+        Ok(old)
+    }
+
+    /// Changes the type of the chunk at `index`, keeping its data. Critical
+    /// types the registry does not know are refused, as decoders would reject
+    /// the file.
+    pub fn rename_chunk(&mut self, index: usize, chunk_type: ChunkType) -> Result<()> {
+        if index >= self.chunks.len() {
+            return Err(Box::from(PngError::IndexOutOfRange(index)));
+        }
+        if !chunk_type.is_valid() {
+            return Err(Box::from(PngError::InvalidChunkType(chunk_type.to_string())));
         }
-        match found {
-            true => Ok(self.chunks.remove(index)),
-            false => Err(Box::from(PngError::ChunkTypeNotFound(String::from(chunk_type)))),
+        if chunk_type.is_critical() && registry::lookup(&chunk_type.to_string()).is_none() {
+            return Err(Box::from(PngError::UnknownCriticalType(chunk_type.to_string())));
         }
+        self.edit(|chunks| {
+            let data = chunks[index].data().to_vec();
+            chunks[index] = Chunk::new(chunk_type, data);
+        })
+    }
+
+    /// Moves the chunk at `from` so that it ends up at index `to`.
+    pub fn move_chunk(&mut self, from: usize, to: usize) -> Result<()> {
+        let count = self.chunks.len();
+        if from >= count || to >= count {
+            return Err(Box::from(PngError::IndexOutOfRange(from.max(to))));
+        }
+        self.edit(|chunks| {
+            let chunk = chunks.remove(from);
+            chunks.insert(to, chunk);
+            apng::renumber(chunks);
+        })
+    }
+
+    // Applies a change to a copy of the chunks, keeping it only if it breaks
+    // no ordering rule the file did not already break and leaves the frames of
+    // an animation intact
+    fn edit<F: FnOnce(&mut Vec<Chunk>)>(&mut self, change: F) -> Result<()> {
+        let mut chunks = self.chunks.clone();
+        change(&mut chunks);
+        let existing = structure_problems(&self.chunks);
+        let mut introduced: Vec<String> = structure_problems(&chunks).into_iter().filter(|p| !existing.contains(p)).collect();
+        introduced.extend(apng::edit_problems(&self.chunks, &chunks));
+        if !introduced.is_empty() {
+            return Err(Box::from(PngError::Structure(introduced.join("; "))));
+        }
+        self.chunks = chunks;
+        Ok(())
     }
 
     pub fn header(&self) -> &[u8; 8] {
@@ -42,17 +146,17 @@ impl Png {
     }
 
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        for chunk in self.chunks.iter() {
-            if chunk.chunk_type().to_string() == chunk_type {
-                return Some(chunk);
-            }
-        }
-        None
+        self.chunks.iter().find(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Every chunk the selector matches, paired with its index in the file.
+    pub fn select<'a>(&'a self, selector: &'a ChunkSelector) -> impl Iterator<Item = (usize, &'a Chunk)> + 'a {
+        self.chunks.iter().enumerate().filter(|(index, chunk)| selector.matches(*index, chunk.chunk_type()))
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut collected: Vec<u8> = vec![];
-        collected.extend(Self::STANDARD_HEADER.iter());
+        collected.extend(self.header().iter());
         for chunk in self.chunks() {
             collected.extend(chunk.as_bytes().iter());
         }
@@ -66,7 +170,7 @@ impl Display for Png {
         let output = self.chunks().iter().map(|x| x.to_string()).fold(String::new(), |mut a, b| {
             a.reserve(b.len() + 1);
             a.push_str(&b);
-            a.push_str("\n");
+            a.push('\n');
             a
         });
 
@@ -77,6 +181,32 @@ impl Display for Png {
 impl TryFrom<&[u8]> for Png {
     type Error = Error;
     fn try_from(value: &[u8]) -> Result<Self> {
+        let raw_chunks = Png::scan(value)?;
+        if raw_chunks.iter().any(|raw| !raw.crc_matches()) {
+            return Err(Box::new(PngError::InvalidFormat));
+        }
+        Ok(Png::from_chunks(raw_chunks.into_iter().map(|raw| raw.chunk).collect()))
+    }
+}
+
+/// A chunk as it was found in a file: where it starts and the CRC stored
+/// alongside it, which may not match the chunk's contents.
+pub struct RawChunk {
+    pub offset: usize,
+    pub chunk: Chunk,
+    pub stored_crc: u32,
+}
+
+impl RawChunk {
+    pub fn crc_matches(&self) -> bool {
+        self.chunk.crc() == self.stored_crc
+    }
+}
+
+impl Png {
+    /// Walks the chunks of a PNG file, checking the signature and chunk framing
+    /// but not the CRCs.
+    pub fn scan(value: &[u8]) -> Result<Vec<RawChunk>> {
         if value.len() < 20 {
             return Err(Box::new(PngError::InvalidFormat));
         }
@@ -84,37 +214,132 @@ impl TryFrom<&[u8]> for Png {
             return Err(Box::new(PngError::InvalidFormat));
         }
 
-        let mut chunks_found: Vec<Chunk> = vec![];
+        let mut chunks_found: Vec<RawChunk> = vec![];
         let mut index: usize = 8;
         while value.len() >= index + 12 {
             let data_size_bytes: [u8; 4] = value[index..index + 4].try_into()?;
             let data_size = u32::from_be_bytes(data_size_bytes) as usize;
-            let potential_chunk_size: usize = 12 + data_size;
-            let potential_chunk = &value[index..index + potential_chunk_size];
-            if let Ok(chunk) = Chunk::try_from(potential_chunk) {
-                chunks_found.push(chunk);
-                index += potential_chunk_size;
-            } else {
+            let crc_index = index + 8 + data_size;
+            if value.len() < crc_index + 4 {
                 return Err(Box::new(PngError::InvalidFormat));
             }
+            let chunk_type_bytes: [u8; 4] = value[index + 4..index + 8].try_into()?;
+            let chunk_type = match ChunkType::try_from(chunk_type_bytes) {
+                Ok(c) => c,
+                Err(_) => return Err(Box::new(PngError::InvalidFormat)),
+            };
+            let chunk = Chunk::new(chunk_type, value[index + 8..crc_index].to_vec());
+            let stored_crc = u32::from_be_bytes(value[crc_index..crc_index + 4].try_into()?);
+            chunks_found.push(RawChunk { offset: index, chunk, stored_crc });
+            index = crc_index + 4;
         }
-        Ok(Png::from_chunks(chunks_found))
+        Ok(chunks_found)
     }
 }
 
 
+/// Lists the ways a chunk sequence breaks the ordering and multiplicity rules
+/// the registry gives for each known chunk type, such as IHDR coming first,
+/// IDAT chunks being consecutive and gAMA coming before PLTE.
+pub fn structure_problems(chunks: &[Chunk]) -> Vec<String> {
+    let types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+    let first = |t: &str| types.iter().position(|x| x == t);
+    let last = |t: &str| types.iter().rposition(|x| x == t);
+    let count = |t: &str| types.iter().filter(|x| *x == t).count();
+    let mut problems: Vec<String> = vec![];
+    let mut add = |problem: String| {
+        if !problems.contains(&problem) {
+            problems.push(problem);
+        }
+    };
+
+    for info in registry::REGISTRY.iter() {
+        let name = info.chunk_type;
+        match info.placement {
+            Placement::First if types.first().map(String::as_str) != Some(name) => add(format!("{} is not the first chunk", name)),
+            Placement::Last if types.last().map(String::as_str) != Some(name) => add(format!("{} is not the last chunk", name)),
+            _ => {},
+        }
+        if !info.multiplicity.allows_several() && count(name) > 1 {
+            add(format!("there is more than one {} chunk", name));
+        }
+    }
+    match (first("IDAT"), last("IDAT")) {
+        (Some(start), Some(end)) if count("IDAT") != end - start + 1 => {
+            add(String::from("the IDAT chunks are not consecutive"));
+        },
+        (None, _) if first("IHDR").is_some() => add(String::from("there is no IDAT chunk")),
+        _ => {},
+    }
+
+    let (plte, idat) = (first("PLTE"), first("IDAT"));
+    for (position, chunk_type) in types.iter().enumerate() {
+        let placement = match registry::lookup(chunk_type) {
+            Some(info) => info.placement,
+            None => continue,
+        };
+        let after = |limit: Option<usize>| limit.is_some_and(|l| position > l);
+        let before = |limit: Option<usize>| limit.is_some_and(|l| position < l);
+        if placement == Placement::BeforePalette && after(plte) {
+            add(format!("{} comes after PLTE", chunk_type));
+        }
+        if placement == Placement::AfterPalette && before(plte) {
+            add(format!("{} comes before PLTE", chunk_type));
+        }
+        if matches!(placement, Placement::BeforePalette | Placement::AfterPalette | Placement::BeforeData) && after(idat) {
+            add(format!("{} comes after IDAT", chunk_type));
+        }
+        if placement == Placement::AfterData && before(idat) {
+            add(format!("{} comes before IDAT", chunk_type));
+        }
+    }
+    problems
+}
+
+/// Everything wrong with the chunks of a file as it was scanned: bad CRCs,
+/// types a decoder would reject or that clash with registered ones, and
+/// broken ordering rules.
+pub fn validation_problems(raw_chunks: &[RawChunk]) -> Vec<String> {
+    let mut problems = vec![];
+    for (index, raw) in raw_chunks.iter().enumerate() {
+        let chunk_type = raw.chunk.chunk_type();
+        let known = registry::lookup(&chunk_type.to_string()).is_some();
+        if !raw.crc_matches() {
+            problems.push(format!("chunk {} ({}) has a bad CRC", index, chunk_type));
+        }
+        if !chunk_type.is_reserved_bit_valid() {
+            problems.push(format!("chunk {} ({}) has the reserved bit set", index, chunk_type));
+        }
+        if chunk_type.is_critical() && !known {
+            problems.push(format!("chunk {} ({}) is an unknown critical chunk, so decoders will reject the file", index, chunk_type));
+        } else if chunk_type.is_public() && !known {
+            problems.push(format!("chunk {} ({}) is public but not a registered chunk type", index, chunk_type));
+        }
+    }
+    let chunks: Vec<Chunk> = raw_chunks.iter().map(|raw| raw.chunk.clone()).collect();
+    problems.extend(structure_problems(&chunks));
+    problems
+}
 
 
 
 #[derive(Debug)]
 pub enum PngError {
-    ChunkTypeNotFound(String),
+    IndexOutOfRange(usize),
+    NoChunksSelected,
+    InvalidChunkType(String),
+    UnknownCriticalType(String),
+    Structure(String),
     InvalidFormat,
 }
 impl Display for PngError {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         match self {
-            PngError::ChunkTypeNotFound(query) => write!(f, "No chunks of type \"{}\" have been found. Check spelling / capitalisation.", query),
+            PngError::IndexOutOfRange(index) => write!(f, "There is no chunk at index {}.", index),
+            PngError::NoChunksSelected => write!(f, "No chunks match the selection."),
+            PngError::InvalidChunkType(chunk_type) => write!(f, "\"{}\" is not a valid chunk type.", chunk_type),
+            PngError::UnknownCriticalType(chunk_type) => write!(f, "{} is an unknown critical chunk type, so decoders would reject the file.", chunk_type),
+            PngError::Structure(problems) => write!(f, "This edit would break the file's structure: {}.", problems),
             PngError::InvalidFormat => write!(f, "This file is not in a valid PNG format."),
         }
     }
@@ -128,19 +353,17 @@ impl std::error::Error for PngError {}
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunk_type::ChunkType;
     use crate::chunk::Chunk;
-    use std::str::FromStr;
+    use crate::image::Image;
     use std::convert::TryFrom;
+    use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -224,6 +447,27 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_scan_keeps_bad_crc() {
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+        let raw_chunks = Png::scan(&bytes).unwrap();
+        assert_eq!(raw_chunks.len(), 3);
+        assert_eq!(raw_chunks[1].offset, 8 + 12 + 20);
+        assert!(raw_chunks[1].crc_matches());
+        assert!(!raw_chunks[2].crc_matches());
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let bytes = testing_png().as_bytes();
+        let png = Png::try_from(&bytes[..bytes.len() - 6]);
+        assert!(png.is_err());
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -249,26 +493,176 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_append_chunk_before_end() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, ["FrSt", "miDl", "LASt", "TeSt", "IEND"]);
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
         png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
-        png.remove_chunk("TeSt").unwrap();
+        png.remove_selected(&"TeSt".parse().unwrap()).unwrap();
         let chunk = png.chunk_by_type("TeSt");
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_select() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "I am a second first chunk").unwrap());
+        let indices: Vec<usize> = png.select(&"FrSt".parse().unwrap()).map(|(i, _)| i).collect();
+        assert_eq!(indices, vec![0, 3]);
+        let indices: Vec<usize> = png.select(&"^[A-Z]".parse().unwrap()).map(|(i, _)| i).collect();
+        assert_eq!(indices, vec![0, 2, 3]);
+        assert_eq!(png.select(&"TeSt".parse().unwrap()).count(), 0);
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = testing_png();
+        png.insert_chunk(1, chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+        png.insert_chunk(4, chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+        assert_eq!(png.chunks().len(), 5);
+        assert!(png.insert_chunk(6, chunk_from_strings("TeSt", "Message").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_remove_chunk_at() {
+        let mut png = testing_png();
+        let chunk = png.remove_chunk_at(1).unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.remove_chunk_at(2).is_err());
+    }
+
+    #[test]
+    fn test_remove_selected() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "I am a second first chunk").unwrap());
+        let selector = "FrSt".parse().unwrap();
+        assert_eq!(png.remove_selected(&selector).unwrap().len(), 2);
+        assert!(png.chunk_by_type("FrSt").is_none());
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.remove_selected(&selector).is_err());
+    }
+
+    fn image_png() -> Png {
+        let mut png = Image::new(1, 1, 8, crate::image::ColourType::Greyscale, vec![0]).unwrap().to_png();
+        png.insert_chunk(1, chunk_from_strings("gAMA", "gama").unwrap()).unwrap();
+        png.insert_chunk(3, chunk_from_strings("tEXt", "a\0b").unwrap()).unwrap();
+        png
+    }
+
+    #[test]
+    fn test_structure_problems() {
+        assert!(structure_problems(image_png().chunks()).is_empty());
+        let problems = structure_problems(testing_png().chunks());
+        assert!(problems.contains(&String::from("IHDR is not the first chunk")));
+        assert!(problems.contains(&String::from("IEND is not the last chunk")));
+    }
+
+    #[test]
+    fn test_multiplicity_problems() {
+        let mut chunks = image_png().chunks().to_vec();
+        chunks.insert(1, chunk_from_strings("gAMA", "gama").unwrap());
+        chunks.insert(5, chunk_from_strings("tEXt", "a\0b").unwrap());
+        assert_eq!(structure_problems(&chunks), vec![String::from("there is more than one gAMA chunk")]);
+    }
+
+    #[test]
+    fn test_insert_keeps_structure() {
+        let mut png = image_png();
+        png.insert_chunk(1, chunk_from_strings("tEXt", "c\0d").unwrap()).unwrap();
+        assert!(png.insert_chunk(1, chunk_from_strings("gAMA", "gama").unwrap()).is_err());
+        assert!(png.insert_chunk(0, chunk_from_strings("ruSt", "").unwrap()).is_err());
+        assert!(png.insert_chunk(png.chunks().len(), chunk_from_strings("IDAT", "").unwrap()).is_err());
+        assert_eq!(png.chunks().len(), image_png().chunks().len() + 1);
+    }
+
+    #[test]
+    fn test_validation_problems() {
+        let mut png = image_png();
+        assert!(validation_problems(&Png::scan(&png.as_bytes()).unwrap()).is_empty());
+        png.insert_chunk(3, chunk_from_strings("ruSt", "").unwrap()).unwrap();
+        png.insert_chunk(3, chunk_from_strings("RUST", "").unwrap()).unwrap();
+        png.insert_chunk(3, chunk_from_strings("tEST", "").unwrap()).unwrap();
+        let problems = validation_problems(&Png::scan(&png.as_bytes()).unwrap());
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("tEST"));
+        assert!(problems[1].contains("unknown critical"));
+    }
+
+    #[test]
+    fn test_replace_chunk_data() {
+        let mut png = image_png();
+        let old = png.replace_chunk_data(3, b"a\0c".to_vec()).unwrap();
+        assert_eq!(old.data(), b"a\0b");
+        assert_eq!(png.chunks()[3].data(), b"a\0c");
+        assert!(png.replace_chunk_data(9, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_rename_chunk() {
+        let mut png = image_png();
+        png.rename_chunk(3, ChunkType::from_str("zTXt").unwrap()).unwrap();
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "zTXt");
+        assert!(png.rename_chunk(3, ChunkType::from_str("IHDR").unwrap()).is_err());
+        assert!(png.rename_chunk(3, ChunkType::from_str("rust").unwrap()).is_err());
+        assert!(png.rename_chunk(3, ChunkType::from_str("RUST").unwrap()).is_err());
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "zTXt");
+    }
+
+    #[test]
+    fn test_removal_keeps_structure() {
+        let mut png = image_png();
+        assert!(png.remove_chunk_at(0).is_err());
+        assert!(png.remove_selected(&"IDAT".parse().unwrap()).is_err());
+        assert_eq!(png.chunks().len(), image_png().chunks().len());
+        png.remove_selected(&"gAMA".parse().unwrap()).unwrap();
+        assert!(png.chunk_by_type("gAMA").is_none());
+    }
+
+    #[test]
+    fn test_move_chunk() {
+        let mut png = image_png();
+        png.move_chunk(3, 1).unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "tEXt");
+        assert!(png.move_chunk(2, 3).is_err());
+        assert!(png.move_chunk(0, 2).is_err());
+    }
+
+    #[test]
+    fn test_edits_allow_existing_problems() {
+        let mut png = testing_png();
+        png.move_chunk(0, 2).unwrap();
+        assert_eq!(&png.chunks()[2].chunk_type().to_string(), "FrSt");
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
         assert!(png.is_ok());
     }
 
+    #[test]
+    fn test_decode_image_from_file() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let image = Image::decode(&png).unwrap();
+        assert_eq!(image.header().width, 50);
+        assert_eq!(image.samples().len(), 50 * 50 * 4);
+    }
+
     #[test]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
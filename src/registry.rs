@@ -0,0 +1,162 @@
+use std::fmt::{Display, Formatter};
+
+/// Where a chunk may appear relative to the other chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    First,
+    Last,
+    /// Before PLTE and the image data
+    BeforePalette,
+    /// After PLTE, if there is one, and before the image data
+    AfterPalette,
+    BeforeData,
+    AfterData,
+    /// In the run of IDAT chunks
+    Data,
+    Anywhere,
+}
+
+impl Display for Placement {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let text = match self {
+            Placement::First => "first chunk",
+            Placement::Last => "last chunk",
+            Placement::BeforePalette => "before PLTE and IDAT",
+            Placement::AfterPalette => "after PLTE, before IDAT",
+            Placement::BeforeData => "before IDAT",
+            Placement::AfterData => "after IDAT",
+            Placement::Data => "consecutive IDAT chunks",
+            Placement::Anywhere => "anywhere",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// How many chunks of a type a file may hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplicity {
+    ExactlyOne,
+    OneOrMore,
+    AtMostOne,
+    Any,
+}
+
+impl Multiplicity {
+    pub fn allows_several(&self) -> bool {
+        matches!(self, Multiplicity::OneOrMore | Multiplicity::Any)
+    }
+}
+
+impl Display for Multiplicity {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let text = match self {
+            Multiplicity::ExactlyOne => "exactly one",
+            Multiplicity::OneOrMore => "one or more",
+            Multiplicity::AtMostOne => "at most one",
+            Multiplicity::Any => "any number",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// What the PNG specification and its common extensions say about a chunk
+/// type.
+pub struct ChunkInfo {
+    pub chunk_type: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub placement: Placement,
+    pub multiplicity: Multiplicity,
+    /// Where the chunk is defined
+    pub source: &'static str,
+}
+
+const fn entry(
+    chunk_type: &'static str,
+    name: &'static str,
+    description: &'static str,
+    placement: Placement,
+    multiplicity: Multiplicity,
+    source: &'static str,
+) -> ChunkInfo {
+    ChunkInfo { chunk_type, name, description, placement, multiplicity, source }
+}
+
+const PNG: &str = "PNG specification";
+const APNG: &str = "APNG (PNG specification, third edition)";
+const EXTENSIONS: &str = "PNG extensions";
+
+use Multiplicity::*;
+use Placement::*;
+
+pub const REGISTRY: [ChunkInfo; 32] = [
+    entry("IHDR", "Image header", "Width, height, bit depth, colour type and interlace method of the image.", First, ExactlyOne, PNG),
+    entry("PLTE", "Palette", "Colours for indexed images, and a suggested palette for truecolour images.", BeforeData, AtMostOne, PNG),
+    entry("IDAT", "Image data", "The compressed, filtered image. Its chunks are concatenated before inflating.", Data, OneOrMore, PNG),
+    entry("IEND", "Image trailer", "Marks the end of the PNG datastream. It has no data.", Last, ExactlyOne, PNG),
+    entry("tRNS", "Transparency", "Alpha for palette entries, or a single fully transparent colour.", AfterPalette, AtMostOne, PNG),
+    entry("cHRM", "Primary chromaticities", "CIE chromaticities of the red, green and blue primaries and the white point.", BeforePalette, AtMostOne, PNG),
+    entry("gAMA", "Image gamma", "The gamma of the encoding, times 100000.", BeforePalette, AtMostOne, PNG),
+    entry("iCCP", "Embedded ICC profile", "A compressed ICC colour profile. It overrides gAMA and cHRM.", BeforePalette, AtMostOne, PNG),
+    entry("sBIT", "Significant bits", "How many bits of each sample were significant in the original image.", BeforePalette, AtMostOne, PNG),
+    entry("sRGB", "Standard RGB colour space", "The image is in the sRGB colour space, with a rendering intent.", BeforePalette, AtMostOne, PNG),
+    entry("cICP", "Coding-independent code points", "Colour primaries, transfer function and matrix as in ITU-T H.273, used for HDR.", BeforePalette, AtMostOne, PNG),
+    entry("mDCv", "Mastering display colour volume", "Primaries, white point and luminance range of the display the image was mastered on.", BeforePalette, AtMostOne, PNG),
+    entry("cLLI", "Content light level information", "Maximum content and frame-average light levels of the image.", BeforePalette, AtMostOne, PNG),
+    entry("tEXt", "Textual data", "A Latin-1 keyword and text.", Anywhere, Any, PNG),
+    entry("zTXt", "Compressed textual data", "A Latin-1 keyword and compressed text.", Anywhere, Any, PNG),
+    entry("iTXt", "International textual data", "A keyword and UTF-8 text, optionally compressed, with a language tag.", Anywhere, Any, PNG),
+    entry("bKGD", "Background colour", "A colour to show the image against.", AfterPalette, AtMostOne, PNG),
+    entry("hIST", "Image histogram", "Approximate usage frequency of each palette entry.", AfterPalette, AtMostOne, PNG),
+    entry("pHYs", "Physical pixel dimensions", "Pixel size or aspect ratio, in pixels per metre or unitless.", BeforeData, AtMostOne, PNG),
+    entry("sPLT", "Suggested palette", "A named palette for displays with a limited number of colours.", BeforeData, Any, PNG),
+    entry("eXIf", "Exchangeable image file profile", "Exif metadata: camera settings, timestamps, and possibly GPS position.", Anywhere, AtMostOne, PNG),
+    entry("tIME", "Image last-modification time", "When the image was last changed, in UTC.", Anywhere, AtMostOne, PNG),
+    entry("acTL", "Animation control", "Number of frames and plays of an animated PNG.", BeforeData, AtMostOne, APNG),
+    entry("fcTL", "Frame control", "Size, position, delay, disposal and blending of an animation frame.", Anywhere, Any, APNG),
+    entry("fdAT", "Frame data", "Image data of an animation frame after the first, with a sequence number.", AfterData, Any, APNG),
+    entry("oFFs", "Image offset", "Position of the image on a page or screen.", BeforeData, AtMostOne, EXTENSIONS),
+    entry("pCAL", "Pixel calibration", "Maps sample values to physical quantities.", BeforeData, AtMostOne, EXTENSIONS),
+    entry("sCAL", "Physical scale", "The physical size of each pixel, in metres or radians.", BeforeData, AtMostOne, EXTENSIONS),
+    entry("sTER", "Stereo image indicator", "The image holds a left and right eye view side by side.", BeforeData, AtMostOne, EXTENSIONS),
+    entry("gIFg", "GIF graphic control extension", "Disposal method, user input flag and delay carried over from a GIF.", Anywhere, Any, EXTENSIONS),
+    entry("gIFx", "GIF application extension", "An application extension block carried over from a GIF.", Anywhere, Any, EXTENSIONS),
+    entry("dSIG", "Digital signature", "A signature over the datastream; pairs of these enclose the signed chunks.", Anywhere, Any, EXTENSIONS),
+];
+
+pub fn lookup(chunk_type: &str) -> Option<&'static ChunkInfo> {
+    REGISTRY.iter().find(|info| info.chunk_type == chunk_type)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_lookup() {
+        let info = lookup("gAMA").unwrap();
+        assert_eq!(info.placement, Placement::BeforePalette);
+        assert_eq!(info.multiplicity, Multiplicity::AtMostOne);
+        assert!(lookup("ruSt").is_none());
+    }
+
+    #[test]
+    fn test_registered_types_are_public_and_valid() {
+        for info in REGISTRY.iter() {
+            let chunk_type = ChunkType::from_str(info.chunk_type).unwrap();
+            assert!(chunk_type.is_valid(), "{}", info.chunk_type);
+            assert!(chunk_type.is_public() || info.chunk_type == "acTL" || info.chunk_type == "fcTL" || info.chunk_type == "fdAT", "{}", info.chunk_type);
+        }
+    }
+
+    #[test]
+    fn test_no_duplicates() {
+        for (i, info) in REGISTRY.iter().enumerate() {
+            assert!(REGISTRY[i + 1..].iter().all(|other| other.chunk_type != info.chunk_type));
+        }
+    }
+}
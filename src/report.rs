@@ -0,0 +1,353 @@
+use std::fmt::{Display, Formatter};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::apng::{self, AnimationSummary};
+use crate::metadata::{ModificationTime, PhysicalDimensions};
+use crate::palette::PaletteEntry;
+use crate::png::{Png, RawChunk};
+use crate::registry;
+use crate::chunk::Chunk;
+use crate::{Error, Result};
+
+// How much of a chunk's data is shown in a preview
+const PREVIEW_BYTES: usize = 16;
+const PREVIEW_CHARS: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Yaml,
+}
+
+/// Everything `print` reports about a single chunk.
+#[derive(Serialize)]
+pub struct ChunkSummary {
+    pub index: usize,
+    pub offset: usize,
+    pub length: u32,
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    pub name: Option<String>,
+    pub valid_type: bool,
+    pub critical: bool,
+    pub public: bool,
+    pub reserved_bit_valid: bool,
+    pub safe_to_copy: bool,
+    pub crc: String,
+    pub crc_valid: bool,
+    pub preview: String,
+}
+
+impl ChunkSummary {
+    pub fn new(index: usize, raw: &RawChunk) -> Self {
+        let chunk_type = raw.chunk.chunk_type();
+        ChunkSummary {
+            index,
+            offset: raw.offset,
+            length: raw.chunk.length(),
+            chunk_type: chunk_type.to_string(),
+            name: registry::lookup(&chunk_type.to_string()).map(|info| info.name.to_string()),
+            valid_type: chunk_type.is_valid(),
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            reserved_bit_valid: chunk_type.is_reserved_bit_valid(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+            crc: format!("{:08x}", raw.stored_crc),
+            crc_valid: raw.crc_matches(),
+            preview: data_preview(raw.chunk.data()),
+        }
+    }
+
+    // One letter per property bit, or '-' where the property does not hold
+    fn flags(&self) -> String {
+        [(self.critical, 'C'), (self.public, 'P'), (self.reserved_bit_valid, 'R'), (self.safe_to_copy, 'S')]
+            .iter()
+            .map(|&(set, letter)| if set { letter } else { '-' })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct FileSummary {
+    pub path: String,
+    pub chunks: Vec<ChunkSummary>,
+    /// The pHYs chunk, with DPI when it is in metres
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub physical: Option<String>,
+    /// The tIME chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    /// The acTL chunk and the frames of an animated PNG
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub animation: Option<AnimationSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Vec<PaletteEntry>>,
+}
+
+impl FileSummary {
+    pub fn new(path: &str, raw_chunks: &[RawChunk]) -> Self {
+        FileSummary {
+            path: path.to_string(),
+            chunks: raw_chunks.iter().enumerate().map(|(i, raw)| ChunkSummary::new(i, raw)).collect(),
+            physical: describe::<PhysicalDimensions>(raw_chunks, PhysicalDimensions::CHUNK_TYPE),
+            modified: describe::<ModificationTime>(raw_chunks, ModificationTime::CHUNK_TYPE),
+            // Broken animation chunks are left to `validate` to explain
+            animation: apng::animation_summary(&Png::from_chunks(raw_chunks.iter().map(|raw| raw.chunk.clone()).collect())).ok().flatten(),
+            palette: None,
+        }
+    }
+}
+
+// The first chunk of a type as its typed value shows it, or why it could not be read
+fn describe<T>(raw_chunks: &[RawChunk], chunk_type: &str) -> Option<String>
+where
+    T: Display + for<'a> TryFrom<&'a Chunk, Error = Error>,
+{
+    let raw = raw_chunks.iter().find(|raw| raw.chunk.chunk_type().to_string() == chunk_type)?;
+    match T::try_from(&raw.chunk) {
+        Ok(value) => Some(value.to_string()),
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+impl Display for FileSummary {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(f, "{}", self.path)?;
+        let name_width = self.chunks.iter().filter_map(|c| c.name.as_ref()).map(|n| n.len()).max().unwrap_or(0).max(4);
+        writeln!(
+            f,
+            "{:>5}  {:>10}  {:>10}  {:<4}  {:<5}  {:<8}  {:<6}  {:<name_width$}  Preview",
+            "Index", "Offset", "Length", "Type", "Flags", "CRC", "Status", "Name",
+        )?;
+        for chunk in self.chunks.iter() {
+            writeln!(
+                f,
+                "{:>5}  {:>10}  {:>10}  {:<4}  {:<5}  {:<8}  {:<6}  {:<name_width$}  {}",
+                chunk.index,
+                chunk.offset,
+                chunk.length,
+                chunk.chunk_type,
+                chunk.flags(),
+                chunk.crc,
+                if chunk.crc_valid { "ok" } else { "BAD" },
+                chunk.name.as_deref().unwrap_or("-"),
+                chunk.preview,
+            )?;
+        }
+        write!(f, "Flags: C = critical, P = public, R = reserved bit valid, S = safe to copy")?;
+        if let Some(physical) = &self.physical {
+            write!(f, "\nPhysical size: {}", physical)?;
+        }
+        if let Some(modified) = &self.modified {
+            write!(f, "\nLast modified: {}", modified)?;
+        }
+        if let Some(animation) = &self.animation {
+            write!(f, "\n{}", AnimationTable(animation))?;
+        }
+        if let Some(palette) = &self.palette {
+            write!(f, "\n{}", PaletteTable(palette))?;
+        }
+        Ok(())
+    }
+}
+
+/// The entries of a palette, one per line with its colour and alpha.
+pub struct PaletteTable<'a>(pub &'a [PaletteEntry]);
+
+impl Display for PaletteTable<'_> {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "Palette: none");
+        }
+        writeln!(f, "Palette: {} entries", self.0.len())?;
+        write!(f, "{:>5}  {:<7}  {:>5}", "Index", "Colour", "Alpha")?;
+        for entry in self.0 {
+            write!(f, "\n{:>5}  {:<7}  {:>5}", entry.index, entry.colour, entry.alpha)?;
+        }
+        Ok(())
+    }
+}
+
+/// The frames of an animation, one per line with its region, timing and data.
+pub struct AnimationTable<'a>(pub &'a AnimationSummary);
+
+impl Display for AnimationTable<'_> {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let plays = match self.0.plays {
+            0 => String::from("plays forever"),
+            1 => String::from("plays once"),
+            n => format!("plays {} times", n),
+        };
+        writeln!(f, "Animation: {} frames, {}", self.0.declared_frames, plays)?;
+        write!(f, "{:>5}  {:>8}  {:<11}  {:<11}  {:>8}  {:<10}  {:<6}  Data", "Frame", "Sequence", "Size", "Offset", "Delay", "Dispose", "Blend")?;
+        for frame in &self.0.frames {
+            write!(
+                f,
+                "\n{:>5}  {:>8}  {:<11}  {:<11}  {:>8}  {:<10}  {:<6}  {} x{}",
+                frame.frame,
+                frame.sequence_number,
+                format!("{}x{}", frame.width, frame.height),
+                format!("{},{}", frame.x_offset, frame.y_offset),
+                format!("{:.3} s", frame.delay_seconds),
+                frame.dispose,
+                frame.blend,
+                frame.data_type,
+                frame.data_chunks,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders a report as its `Display` text for tables, or serialized otherwise.
+pub fn render<T: Serialize + Display>(report: &T, format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Table => Ok(report.to_string()),
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(report)?),
+        OutputFormat::Yaml => Ok(serde_yaml::to_string(report)?),
+    }
+}
+
+/// Renders one of several reports written one after another: JSON goes on
+/// a single line, so that the output is newline-delimited JSON, and each
+/// YAML document starts with `---`.
+pub fn render_in_batch<T: Serialize + Display>(report: &T, format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Table => Ok(report.to_string()),
+        OutputFormat::Json => Ok(serde_json::to_string(report)?),
+        OutputFormat::Yaml => Ok(format!("---\n{}", serde_yaml::to_string(report)?.trim_end())),
+    }
+}
+
+/// Shows printable data as quoted text and anything else as hex, truncated
+/// either way.
+pub fn data_preview(data: &[u8]) -> String {
+    let printable = data.iter().all(|&b| (0x20..0x7f).contains(&b) || b == b'\n' || b == b'\t' || b == b'\r');
+    if printable {
+        let text: String = data.iter().take(PREVIEW_CHARS).map(|&b| b as char).collect();
+        let text = text.escape_default().to_string();
+        let ellipsis = if data.len() > PREVIEW_CHARS { "..." } else { "" };
+        format!("\"{}\"{}", text, ellipsis)
+    } else {
+        let hex: Vec<String> = data.iter().take(PREVIEW_BYTES).map(|b| format!("{:02x}", b)).collect();
+        let ellipsis = if data.len() > PREVIEW_BYTES { " ..." } else { "" };
+        format!("{}{}", hex.join(" "), ellipsis)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::png::Png;
+    use std::str::FromStr;
+
+    fn testing_summary() -> FileSummary {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hidden".to_vec()),
+        ];
+        let bytes = Png::from_chunks(chunks).as_bytes();
+        FileSummary::new("test.png", &Png::scan(&bytes).unwrap())
+    }
+
+    #[test]
+    fn test_data_preview() {
+        assert_eq!(data_preview(b"hello"), "\"hello\"");
+        assert_eq!(data_preview(&[0, 1, 255]), "00 01 ff");
+        assert!(data_preview(&[0; 20]).ends_with(" ..."));
+        assert!(data_preview(&[b'a'; 50]).ends_with("\"..."));
+    }
+
+    #[test]
+    fn test_chunk_summary() {
+        let summary = testing_summary();
+        let chunk = &summary.chunks[1];
+        assert_eq!(chunk.offset, 8 + 12 + 13);
+        assert_eq!(chunk.flags(), "--RS");
+        assert!(chunk.crc_valid);
+    }
+
+    #[test]
+    fn test_render_json() {
+        let json = render(&testing_summary(), OutputFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["chunks"][1]["type"], "ruSt");
+        assert_eq!(value["chunks"][0]["critical"], true);
+        assert_eq!(value["chunks"][0]["name"], "Image header");
+        assert!(value["chunks"][1]["name"].is_null());
+    }
+
+    #[test]
+    fn test_palette_table() {
+        let mut summary = testing_summary();
+        assert!(!render(&summary, OutputFormat::Json).unwrap().contains("palette"));
+        summary.palette = Some(vec![PaletteEntry { index: 0, colour: String::from("#ff0000"), red: 255, green: 0, blue: 0, alpha: 128 }]);
+        let table = summary.to_string();
+        assert!(table.ends_with("Palette: 1 entries\nIndex  Colour   Alpha\n    0  #ff0000    128"));
+    }
+
+    #[test]
+    fn test_physical_size_and_time() {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("pHYs").unwrap(), vec![0, 0, 11, 19, 0, 0, 11, 19, 1]),
+            Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![7, 232, 2, 29, 9, 5, 0]),
+        ];
+        let summary = FileSummary::new("test.png", &Png::scan(&Png::from_chunks(chunks).as_bytes()).unwrap());
+        let table = summary.to_string();
+        assert!(table.contains("\nPhysical size: 2835 pixels per metre (72 DPI)"));
+        assert!(table.ends_with("\nLast modified: 2024-02-29 09:05:00 UTC"));
+        assert!(!render(&testing_summary(), OutputFormat::Json).unwrap().contains("physical"));
+    }
+
+    #[test]
+    fn test_animation_table() {
+        let mut summary = testing_summary();
+        assert!(summary.animation.is_none());
+        summary.animation = Some(AnimationSummary {
+            declared_frames: 1,
+            plays: 2,
+            frames: vec![apng::FrameEntry {
+                frame: 0,
+                sequence_number: 0,
+                width: 1,
+                height: 1,
+                x_offset: 0,
+                y_offset: 0,
+                delay_seconds: 0.5,
+                dispose: String::from("none"),
+                blend: String::from("over"),
+                data_type: String::from("IDAT"),
+                data_chunks: 1,
+            }],
+        });
+        let table = summary.to_string();
+        assert!(table.contains("\nAnimation: 1 frames, plays 2 times\n"));
+        assert!(table.ends_with("    0         0  1x1          0,0           0.500 s  none        over    IDAT x1"));
+    }
+
+    #[test]
+    fn test_render_yaml() {
+        let yaml = render(&testing_summary(), OutputFormat::Yaml).unwrap();
+        assert!(yaml.contains("type: ruSt"));
+    }
+
+    #[test]
+    fn test_render_in_batch() {
+        let json = render_in_batch(&testing_summary(), OutputFormat::Json).unwrap();
+        assert!(!json.contains('\n'));
+        let lines = [json.clone(), json].join("\n");
+        assert_eq!(lines.lines().filter_map(|l| serde_json::from_str::<serde_json::Value>(l).ok()).count(), 2);
+
+        let yaml = render_in_batch(&testing_summary(), OutputFormat::Yaml).unwrap();
+        assert!(yaml.starts_with("---\npath: test.png\n"));
+        let stream = [yaml.clone(), yaml].join("\n");
+        assert_eq!(serde_yaml::Deserializer::from_str(&stream).count(), 2);
+    }
+}
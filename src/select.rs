@@ -0,0 +1,178 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use glob::Pattern;
+use regex::Regex;
+
+use crate::chunk_type::ChunkType;
+
+/// One test a chunk must pass to be selected.
+#[derive(Clone, Debug)]
+pub enum Criterion {
+    Index(usize),
+    Type(String),
+    Glob(Pattern),
+    Regex(Regex),
+    Critical(bool),
+    Public(bool),
+    SafeToCopy(bool),
+}
+
+impl Criterion {
+    fn matches(&self, index: usize, chunk_type: &ChunkType) -> bool {
+        match self {
+            Criterion::Index(i) => *i == index,
+            Criterion::Type(t) => chunk_type.to_string() == *t,
+            Criterion::Glob(pattern) => pattern.matches(&chunk_type.to_string()),
+            Criterion::Regex(regex) => regex.is_match(&chunk_type.to_string()),
+            Criterion::Critical(critical) => chunk_type.is_critical() == *critical,
+            Criterion::Public(public) => chunk_type.is_public() == *public,
+            Criterion::SafeToCopy(safe) => chunk_type.is_safe_to_copy() == *safe,
+        }
+    }
+}
+
+/// Picks out chunks by index, type, a glob or regular expression on the type,
+/// or the properties encoded in the type. Terms are joined with commas and a
+/// chunk must match all of them, so `private,r???` selects private chunks
+/// whose type starts with `r`.
+///
+/// A regular expression is written with a leading `^` or a `re:` prefix and
+/// takes up the rest of the selector, since it may itself contain commas.
+#[derive(Clone, Debug)]
+pub struct ChunkSelector {
+    criteria: Vec<Criterion>,
+}
+
+impl ChunkSelector {
+    pub fn matches(&self, index: usize, chunk_type: &ChunkType) -> bool {
+        self.criteria.iter().all(|criterion| criterion.matches(index, chunk_type))
+    }
+}
+
+impl FromStr for ChunkSelector {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut criteria = vec![];
+        let mut rest = s;
+        while !rest.is_empty() || criteria.is_empty() {
+            if let Some(expression) = rest.strip_prefix("re:").or(rest.starts_with('^').then_some(rest)) {
+                match Regex::new(expression) {
+                    Ok(regex) => criteria.push(Criterion::Regex(regex)),
+                    Err(e) => return Err(SelectorError::Pattern(e.to_string())),
+                }
+                break;
+            }
+            let (term, remainder) = rest.split_once(',').unwrap_or((rest, ""));
+            criteria.push(parse_term(term)?);
+            rest = remainder;
+        }
+        Ok(ChunkSelector { criteria })
+    }
+}
+
+fn parse_term(term: &str) -> Result<Criterion, SelectorError> {
+    let criterion = match term {
+        "critical" => Criterion::Critical(true),
+        "ancillary" => Criterion::Critical(false),
+        "public" => Criterion::Public(true),
+        "private" => Criterion::Public(false),
+        "safe-to-copy" => Criterion::SafeToCopy(true),
+        "unsafe-to-copy" => Criterion::SafeToCopy(false),
+        _ if !term.is_empty() && term.bytes().all(|b| b.is_ascii_digit()) => match term.parse() {
+            Ok(index) => Criterion::Index(index),
+            Err(_) => return Err(SelectorError::Term(term.to_string())),
+        },
+        _ if term.contains(['*', '?', '[']) => match Pattern::new(term) {
+            Ok(pattern) => Criterion::Glob(pattern),
+            Err(e) => return Err(SelectorError::Pattern(e.to_string())),
+        },
+        _ => match ChunkType::from_str(term) {
+            Ok(chunk_type) => Criterion::Type(chunk_type.to_string()),
+            Err(_) => return Err(SelectorError::Term(term.to_string())),
+        },
+    };
+    Ok(criterion)
+}
+
+
+
+#[derive(Debug)]
+pub enum SelectorError {
+    Term(String),
+    Pattern(String),
+}
+impl Display for SelectorError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            SelectorError::Term(term) => write!(f, "\"{}\" is not a chunk index, chunk type, pattern or property.", term),
+            SelectorError::Pattern(e) => write!(f, "Invalid pattern: {}", e),
+        }
+    }
+}
+impl std::error::Error for SelectorError {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selects(selector: &str, index: usize, chunk_type: &str) -> bool {
+        let selector = ChunkSelector::from_str(selector).unwrap();
+        selector.matches(index, &ChunkType::from_str(chunk_type).unwrap())
+    }
+
+    #[test]
+    fn test_exact_type() {
+        assert!(selects("ruSt", 0, "ruSt"));
+        assert!(!selects("ruSt", 0, "RuSt"));
+    }
+
+    #[test]
+    fn test_index() {
+        assert!(selects("3", 3, "IDAT"));
+        assert!(!selects("3", 4, "IDAT"));
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(selects("r???", 0, "ruSt"));
+        assert!(!selects("r???", 0, "RuSt"));
+        assert!(selects("[IP]*", 0, "PLTE"));
+    }
+
+    #[test]
+    fn test_regex() {
+        assert!(selects("^[a-z]", 0, "ruSt"));
+        assert!(!selects("^[a-z]", 0, "IHDR"));
+        assert!(selects("re:T$", 0, "tEXT"));
+        assert!(selects("re:^(IDAT|fdAT)$", 0, "fdAT"));
+    }
+
+    #[test]
+    fn test_properties() {
+        assert!(selects("ancillary", 0, "ruSt"));
+        assert!(!selects("ancillary", 0, "IHDR"));
+        assert!(selects("private", 0, "ruSt"));
+        assert!(!selects("private", 0, "tEXt"));
+        assert!(selects("unsafe-to-copy", 0, "tEXT"));
+    }
+
+    #[test]
+    fn test_combined_terms() {
+        assert!(selects("private,r???", 0, "ruSt"));
+        assert!(!selects("private,r???", 0, "rUSt"));
+        assert!(selects("ancillary,^[a-z]{2,3}", 0, "ruSt"));
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        assert!(ChunkSelector::from_str("").is_err());
+        assert!(ChunkSelector::from_str("nonsense").is_err());
+        assert!(ChunkSelector::from_str("ru1t").is_err());
+        assert!(ChunkSelector::from_str("^[a-").is_err());
+        assert!(ChunkSelector::from_str("ruSt,").is_ok());
+    }
+}
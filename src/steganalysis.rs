@@ -0,0 +1,380 @@
+use std::fmt::{Display, Formatter};
+
+use crate::image::{ColourType, Image};
+use crate::Result;
+
+// Number of prefixes of a channel tested by the sequential chi-square attack
+const CHI_SQUARE_STEPS: usize = 100;
+// Mask applied to each group of pixels by the RS analysis
+const RS_MASK: [i8; 4] = [0, 1, 1, 0];
+
+/// The outcome of the chi-square (pairs of values) attack on one channel.
+pub struct ChiSquare {
+    pub statistic: f64,
+    /// Probability that the whole channel carries LSB-embedded data.
+    pub probability: f64,
+    /// Fraction of the channel, read in scan order, whose prefix still looks embedded.
+    pub sequential_rate: f64,
+}
+
+/// The outcome of the regular/singular groups analysis on one channel.
+pub struct RegularSingular {
+    pub regular: f64,
+    pub singular: f64,
+    pub regular_negative: f64,
+    pub singular_negative: f64,
+    /// Estimated fraction of samples whose least significant bit carries payload.
+    pub rate: f64,
+}
+
+pub struct ChannelAnalysis {
+    pub name: &'static str,
+    pub chi_square: ChiSquare,
+    pub rs: RegularSingular,
+}
+
+pub struct Analysis {
+    pub channels: Vec<ChannelAnalysis>,
+}
+
+impl Analysis {
+    pub fn of_image(image: &Image) -> Analysis {
+        let header = image.header();
+        let width = header.width as usize;
+        let channels = header
+            .colour_type
+            .channel_names()
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let samples = image.channel(i);
+                ChannelAnalysis {
+                    name,
+                    chi_square: chi_square(&samples, header.bit_depth),
+                    rs: regular_singular(&samples, width),
+                }
+            })
+            .collect();
+        Analysis { channels }
+    }
+
+    /// The mean RS estimate over the colour channels (alpha is left out, as it is
+    /// rarely used for embedding and is often constant).
+    pub fn embedding_rate(&self) -> f64 {
+        let colour: Vec<&ChannelAnalysis> = self.channels.iter().filter(|c| c.name != "alpha").collect();
+        if colour.is_empty() {
+            return 0.0;
+        }
+        colour.iter().map(|c| c.rs.rate).sum::<f64>() / colour.len() as f64
+    }
+}
+
+impl Display for Analysis {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for channel in self.channels.iter() {
+            writeln!(
+                f,
+                "{:<6} chi-square {:.2} (p = {:.3}, sequential rate {:.1}%), RS R/S {:.3}/{:.3} R-/S- {:.3}/{:.3} (rate {:.1}%)",
+                channel.name,
+                channel.chi_square.statistic,
+                channel.chi_square.probability,
+                channel.chi_square.sequential_rate * 100.0,
+                channel.rs.regular,
+                channel.rs.singular,
+                channel.rs.regular_negative,
+                channel.rs.singular_negative,
+                channel.rs.rate * 100.0,
+            )?;
+        }
+        write!(f, "Estimated embedding rate: {:.1}%", self.embedding_rate() * 100.0)
+    }
+}
+
+/// Runs the Westfeld-Pfitzmann chi-square attack on the samples of one channel.
+pub fn chi_square(samples: &[u16], bit_depth: u8) -> ChiSquare {
+    let mut histogram = vec![0u64; 1 << bit_depth];
+    let mut embedded_steps = 0;
+    let mut last = (0.0, 0.0);
+    for step in 1..=CHI_SQUARE_STEPS {
+        let start = samples.len() * (step - 1) / CHI_SQUARE_STEPS;
+        let end = samples.len() * step / CHI_SQUARE_STEPS;
+        for &sample in &samples[start..end] {
+            histogram[sample as usize] += 1;
+        }
+        last = pairs_of_values(&histogram);
+        if last.1 > 0.5 {
+            embedded_steps += 1;
+        }
+    }
+    ChiSquare {
+        statistic: last.0,
+        probability: last.1,
+        sequential_rate: embedded_steps as f64 / CHI_SQUARE_STEPS as f64,
+    }
+}
+
+// Returns the chi-square statistic and embedding probability of a histogram
+fn pairs_of_values(histogram: &[u64]) -> (f64, f64) {
+    let mut statistic = 0.0;
+    let mut categories = 0;
+    for pair in histogram.chunks(2) {
+        let expected = (pair.iter().sum::<u64>()) as f64 / 2.0;
+        if expected > 0.0 {
+            statistic += (pair[0] as f64 - expected).powi(2) / expected;
+            categories += 1;
+        }
+    }
+    if categories < 2 {
+        return (statistic, 0.0);
+    }
+    let degrees_of_freedom = (categories - 1) as f64;
+    (statistic, 1.0 - regularised_gamma(degrees_of_freedom / 2.0, statistic / 2.0))
+}
+
+/// Runs Fridrich's RS analysis on the samples of one channel, grouping
+/// horizontally adjacent samples within each row.
+pub fn regular_singular(samples: &[u16], width: usize) -> RegularSingular {
+    let negative_mask = RS_MASK.map(|m| -m);
+    let flipped: Vec<u16> = samples.iter().map(|s| s ^ 1).collect();
+
+    let (r_m, s_m) = count_groups(samples, width, &RS_MASK);
+    let (r_neg, s_neg) = count_groups(samples, width, &negative_mask);
+    let (r_m_flipped, s_m_flipped) = count_groups(&flipped, width, &RS_MASK);
+    let (r_neg_flipped, s_neg_flipped) = count_groups(&flipped, width, &negative_mask);
+
+    let d0 = r_m - s_m;
+    let d1 = r_m_flipped - s_m_flipped;
+    let d0_neg = r_neg - s_neg;
+    let d1_neg = r_neg_flipped - s_neg_flipped;
+
+    let a = 2.0 * (d1 + d0);
+    let b = d0_neg - d1_neg - d1 - 3.0 * d0;
+    let c = d0 - d0_neg;
+    let root = if a.abs() < f64::EPSILON {
+        if b.abs() < f64::EPSILON { 0.0 } else { -c / b }
+    } else {
+        let discriminant = (b * b - 4.0 * a * c).max(0.0).sqrt();
+        let (x1, x2) = ((-b + discriminant) / (2.0 * a), (-b - discriminant) / (2.0 * a));
+        if x1.abs() < x2.abs() { x1 } else { x2 }
+    };
+    let rate = if (root - 0.5).abs() < f64::EPSILON { 1.0 } else { root / (root - 0.5) };
+
+    RegularSingular {
+        regular: r_m,
+        singular: s_m,
+        regular_negative: r_neg,
+        singular_negative: s_neg,
+        rate: if rate.is_finite() { rate.clamp(0.0, 1.0) } else { 0.0 },
+    }
+}
+
+// Returns the fractions of groups that are regular and singular under a mask
+fn count_groups(samples: &[u16], width: usize, mask: &[i8; 4]) -> (f64, f64) {
+    let (mut regular, mut singular, mut total) = (0u64, 0u64, 0u64);
+    for row in samples.chunks(width) {
+        for group in row.chunks_exact(mask.len()) {
+            let original: Vec<i32> = group.iter().map(|&s| s as i32).collect();
+            let flipped: Vec<i32> = original.iter().zip(mask.iter()).map(|(&s, &m)| flip(s, m)).collect();
+            let (before, after) = (smoothness(&original), smoothness(&flipped));
+            if after > before {
+                regular += 1;
+            } else if after < before {
+                singular += 1;
+            }
+            total += 1;
+        }
+    }
+    if total == 0 {
+        return (0.0, 0.0);
+    }
+    (regular as f64 / total as f64, singular as f64 / total as f64)
+}
+
+fn flip(sample: i32, direction: i8) -> i32 {
+    match direction {
+        1 => sample ^ 1,
+        -1 => ((sample + 1) ^ 1) - 1,
+        _ => sample,
+    }
+}
+
+fn smoothness(group: &[i32]) -> i32 {
+    group.windows(2).map(|w| (w[1] - w[0]).abs()).sum()
+}
+
+// Regularised lower incomplete gamma function P(a, x)
+fn regularised_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x < a + 1.0 {
+        let (mut sum, mut term, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..1000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (sum.ln() - x + a * x.ln() - ln_gamma(a)).exp()
+    } else {
+        // Lentz's method for the continued fraction of Q(a, x)
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        1.0 - (-x + a * x.ln() - ln_gamma(a)).exp() * h
+    }
+}
+
+// Lanczos approximation of ln(Γ(x))
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let mut y = x;
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for coefficient in COEFFICIENTS {
+        y += 1.0;
+        series += coefficient / y;
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Builds an 8-bit image showing the least significant bit of every colour
+/// channel, scaled to black or white. Alpha is dropped and palette indices are
+/// shown as greyscale.
+pub fn lsb_plane(image: &Image) -> Result<Image> {
+    let header = image.header();
+    let colour_type = match header.colour_type {
+        ColourType::Truecolour | ColourType::TruecolourAlpha => ColourType::Truecolour,
+        _ => ColourType::Greyscale,
+    };
+    let channels = header.colour_type.channels();
+    let kept = channels - header.colour_type.has_alpha() as usize;
+    let samples = image
+        .samples()
+        .chunks(channels)
+        .flat_map(|pixel| pixel[..kept].iter().map(|s| if s & 1 == 1 { 255 } else { 0 }))
+        .collect();
+    Image::new(header.width, header.height, 8, colour_type, samples)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small xorshift generator so the tests do not depend on a random crate
+    struct Noise(u32);
+    impl Noise {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+    }
+
+    fn natural_image() -> Image {
+        let (width, height) = (128u32, 128u32);
+        let mut noise = Noise(2463534242);
+        let samples = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f64, (i / width) as f64);
+                let value = 128.0 + 60.0 * (x / 11.0).sin() * (y / 17.0).cos() + (noise.next() % 5) as f64;
+                value as u16
+            })
+            .collect();
+        Image::new(width, height, 8, ColourType::Greyscale, samples).unwrap()
+    }
+
+    fn embed(image: &Image, rate: f64) -> Image {
+        let mut noise = Noise(88675123);
+        let count = (image.samples().len() as f64 * rate) as usize;
+        let samples = image
+            .samples()
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| if i < count { (s & !1) | (noise.next() & 1) as u16 } else { s })
+            .collect();
+        let header = image.header();
+        Image::new(header.width, header.height, header.bit_depth, header.colour_type, samples).unwrap()
+    }
+
+    #[test]
+    fn test_regularised_gamma() {
+        assert!((regularised_gamma(1.0, 1.0) - (1.0 - (-1.0f64).exp())).abs() < 1e-9);
+        assert!((regularised_gamma(5.0, 20.0) - 0.9999831).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_clean_image() {
+        let analysis = Analysis::of_image(&natural_image());
+        assert!(analysis.channels[0].rs.rate < 0.15);
+    }
+
+    #[test]
+    fn test_chi_square() {
+        // Only even values, so every pair of values is as unbalanced as it can be
+        let samples: Vec<u16> = natural_image().samples().iter().map(|s| s & !1).collect();
+        assert!(chi_square(&samples, 8).probability < 0.01);
+
+        let image = Image::new(128, 128, 8, ColourType::Greyscale, samples).unwrap();
+        let embedded = embed(&image, 0.5).channel(0);
+        let result = chi_square(&embedded, 8);
+        assert!(result.sequential_rate > 0.4 && result.sequential_rate < 0.6);
+    }
+
+    #[test]
+    fn test_fully_embedded_image() {
+        let analysis = Analysis::of_image(&embed(&natural_image(), 1.0));
+        let channel = &analysis.channels[0];
+        assert!(channel.chi_square.probability > 0.9);
+        assert!(channel.chi_square.sequential_rate > 0.9);
+        assert!(analysis.embedding_rate() > 0.7);
+    }
+
+    #[test]
+    fn test_partially_embedded_image() {
+        let clean = Analysis::of_image(&natural_image()).embedding_rate();
+        let partial = Analysis::of_image(&embed(&natural_image(), 0.5)).embedding_rate();
+        assert!(partial > clean + 0.2);
+    }
+
+    #[test]
+    fn test_lsb_plane() {
+        let image = Image::new(2, 1, 8, ColourType::TruecolourAlpha, vec![1, 2, 3, 255, 4, 5, 6, 0]).unwrap();
+        let plane = lsb_plane(&image).unwrap();
+        assert_eq!(plane.header().colour_type, ColourType::Truecolour);
+        assert_eq!(plane.samples(), &[255, 0, 255, 0, 255, 0]);
+    }
+}
@@ -0,0 +1,103 @@
+use clap::ValueEnum;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::select::ChunkSelector;
+use crate::Result;
+
+// Chunks that can identify a person, a device or a place
+const PRIVATE_METADATA: [&str; 7] = ["eXIf", "tEXt", "zTXt", "iTXt", "tIME", "iCCP", "dSIG"];
+const COLOUR_MANAGEMENT: [&str; 8] = ["cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCv", "cLLI"];
+// Ancillary chunks that change what is displayed rather than describe it
+const DISPLAY: [&str; 4] = ["tRNS", "acTL", "fcTL", "fdAT"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Every ancillary chunk
+    All,
+    /// Text, EXIF, timestamps, ICC profiles, signatures and private chunks
+    Privacy,
+    /// Every ancillary chunk except those describing colour
+    KeepColour,
+}
+
+impl Preset {
+    fn removes(&self, chunk_type: &ChunkType) -> bool {
+        let name = chunk_type.to_string();
+        if chunk_type.is_critical() || DISPLAY.contains(&name.as_str()) {
+            return false;
+        }
+        match self {
+            Preset::All => true,
+            Preset::Privacy => PRIVATE_METADATA.contains(&name.as_str()) || !chunk_type.is_public(),
+            Preset::KeepColour => !COLOUR_MANAGEMENT.contains(&name.as_str()),
+        }
+    }
+}
+
+/// Removes the ancillary chunks a preset picks, plus any the `remove` selectors
+/// pick, except those a `keep` selector picks. Critical chunks are never
+/// removed, and transparency and animation chunks only when asked for by a
+/// `remove` selector. Returns the removed chunks with their old indices.
+pub fn strip(png: &mut Png, preset: Preset, keep: &[ChunkSelector], remove: &[ChunkSelector]) -> Result<Vec<(usize, Chunk)>> {
+    let selected: Vec<usize> = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(index, chunk)| {
+            let chunk_type = chunk.chunk_type();
+            let selected = preset.removes(chunk_type) || remove.iter().any(|s| s.matches(*index, chunk_type));
+            selected && !chunk_type.is_critical() && !keep.iter().any(|s| s.matches(*index, chunk_type))
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    let removed = png.remove_chunks_at(&selected)?;
+    Ok(selected.into_iter().zip(removed).collect())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        let chunks = ["IHDR", "gAMA", "iCCP", "pHYs", "tRNS", "IDAT", "tEXt", "ruSt", "eXIf", "IEND"]
+            .iter()
+            .map(|t| Chunk::new(ChunkType::from_str(t).unwrap(), vec![]))
+            .collect();
+        Png::from_chunks(chunks)
+    }
+
+    fn removed_types(preset: Preset, keep: &[&str], remove: &[&str]) -> Vec<String> {
+        let parse = |selectors: &[&str]| selectors.iter().map(|s| ChunkSelector::from_str(s).unwrap()).collect::<Vec<_>>();
+        let mut png = testing_png();
+        let removed = strip(&mut png, preset, &parse(keep), &parse(remove)).unwrap();
+        assert_eq!(png.chunks().len() + removed.len(), 10);
+        removed.iter().map(|(_, c)| c.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(removed_types(Preset::All, &[], &[]), vec!["gAMA", "iCCP", "pHYs", "tEXt", "ruSt", "eXIf"]);
+        assert_eq!(removed_types(Preset::Privacy, &[], &[]), vec!["iCCP", "tEXt", "ruSt", "eXIf"]);
+        assert_eq!(removed_types(Preset::KeepColour, &[], &[]), vec!["pHYs", "tEXt", "ruSt", "eXIf"]);
+    }
+
+    #[test]
+    fn test_keep_and_remove_lists() {
+        assert_eq!(removed_types(Preset::Privacy, &["iCCP", "private"], &["pHYs"]), vec!["pHYs", "tEXt", "eXIf"]);
+        assert_eq!(removed_types(Preset::Privacy, &[], &["tRNS", "IHDR"]), vec!["iCCP", "tRNS", "tEXt", "ruSt", "eXIf"]);
+    }
+
+    #[test]
+    fn test_removed_indices() {
+        let mut png = testing_png();
+        let removed = strip(&mut png, Preset::Privacy, &[], &[]).unwrap();
+        let indices: Vec<usize> = removed.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices, vec![2, 6, 7, 8]);
+    }
+}
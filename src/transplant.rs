@@ -0,0 +1,226 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chunk::Chunk;
+use crate::png::{self, Png};
+use crate::registry::{self, Placement};
+use crate::select::ChunkSelector;
+use crate::Result;
+
+pub struct TransplantOptions {
+    /// Copy unsafe-to-copy chunks even though the critical chunks differ
+    pub allow_unsafe: bool,
+    /// Overwrite a chunk the destination may only hold one of
+    pub replace_existing: bool,
+}
+
+/// What happened to one ancillary chunk of the source file.
+pub enum Decision {
+    Copied,
+    Replaced,
+    Unsafe,
+    Duplicate,
+    AlreadyPresent,
+}
+
+pub struct Transplanted {
+    pub chunk_type: String,
+    pub decision: Decision,
+}
+
+impl Display for Transplanted {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self.decision {
+            Decision::Copied => write!(f, "Copied {}", self.chunk_type),
+            Decision::Replaced => write!(f, "Replaced {}", self.chunk_type),
+            Decision::Unsafe => write!(f, "Skipped {}: unsafe to copy and the critical chunks differ", self.chunk_type),
+            Decision::Duplicate => write!(f, "Skipped {}: the destination already has an identical chunk", self.chunk_type),
+            Decision::AlreadyPresent => write!(f, "Skipped {}: the destination already has one", self.chunk_type),
+        }
+    }
+}
+
+// Where an ancillary chunk sits relative to the image data
+#[derive(Clone, Copy)]
+enum Region {
+    BeforePalette,
+    BeforeData,
+    AfterData,
+}
+
+fn position_of(chunks: &[Chunk], chunk_type: &str) -> Option<usize> {
+    chunks.iter().position(|c| c.chunk_type().to_string() == chunk_type)
+}
+
+// The region the registry puts a chunk type in, or else where the chunk sat in
+// the file it came from
+fn region_of(chunks: &[Chunk], index: usize) -> Region {
+    let placement = registry::lookup(&chunks[index].chunk_type().to_string()).map(|info| info.placement);
+    match placement {
+        Some(Placement::BeforePalette) => return Region::BeforePalette,
+        Some(Placement::AfterPalette | Placement::BeforeData) => return Region::BeforeData,
+        Some(Placement::AfterData) => return Region::AfterData,
+        _ => {},
+    }
+    match (position_of(chunks, "PLTE"), position_of(chunks, "IDAT")) {
+        (Some(palette), _) if index < palette => Region::BeforePalette,
+        (_, Some(data)) if index < data => Region::BeforeData,
+        (_, None) => Region::BeforeData,
+        _ => Region::AfterData,
+    }
+}
+
+// Where a chunk from `region` goes in the destination
+fn insertion_point(chunks: &[Chunk], region: Region) -> usize {
+    let end = match chunks.last() {
+        Some(last) if last.chunk_type().to_string() == "IEND" => chunks.len() - 1,
+        _ => chunks.len(),
+    };
+    let data = position_of(chunks, "IDAT").unwrap_or(end);
+    match region {
+        Region::BeforePalette => position_of(chunks, "PLTE").unwrap_or(data),
+        Region::BeforeData => data,
+        Region::AfterData => end,
+    }
+}
+
+fn critical_chunks(png: &Png) -> Vec<&Chunk> {
+    png.chunks().iter().filter(|c| c.chunk_type().is_critical()).collect()
+}
+
+/// Copies the ancillary chunks of `source`, or those the selector picks, into
+/// `destination`. Safe-to-copy chunks are always copied. Unsafe-to-copy ones
+/// depend on the image data, so they are only copied when both files have the
+/// same critical chunks or `allow_unsafe` is set. Each copy goes where the
+/// registry places its type, or keeps its place relative to PLTE and IDAT
+/// when the type may go anywhere, and the edit is refused if it would break
+/// the destination's chunk ordering.
+pub fn transplant(source: &Png, destination: &mut Png, selector: Option<&ChunkSelector>, options: &TransplantOptions) -> Result<Vec<Transplanted>> {
+    let same_critical = critical_chunks(source).iter().map(|c| c.as_bytes()).eq(critical_chunks(destination).iter().map(|c| c.as_bytes()));
+    let mut chunks = destination.chunks().to_vec();
+    let mut report = vec![];
+
+    for (index, chunk) in source.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        if chunk_type.is_critical() || selector.is_some_and(|s| !s.matches(index, chunk_type)) {
+            continue;
+        }
+        let name = chunk_type.to_string();
+        let decision = if !chunk_type.is_safe_to_copy() && !same_critical && !options.allow_unsafe {
+            Decision::Unsafe
+        } else if chunks.iter().any(|c| c.chunk_type() == chunk_type && c.data() == chunk.data()) {
+            Decision::Duplicate
+        } else {
+            match position_of(&chunks, &name) {
+                Some(existing) if registry::lookup(&name).is_some_and(|info| !info.multiplicity.allows_several()) => {
+                    if options.replace_existing {
+                        chunks[existing] = chunk.clone();
+                        Decision::Replaced
+                    } else {
+                        Decision::AlreadyPresent
+                    }
+                },
+                _ => {
+                    let at = insertion_point(&chunks, region_of(source.chunks(), index));
+                    chunks.insert(at, chunk.clone());
+                    Decision::Copied
+                },
+            }
+        };
+        report.push(Transplanted { chunk_type: name, decision });
+    }
+
+    let existing = png::structure_problems(destination.chunks());
+    let introduced: Vec<String> = png::structure_problems(&chunks).into_iter().filter(|p| !existing.contains(p)).collect();
+    if !introduced.is_empty() {
+        return Err(Box::new(png::PngError::Structure(introduced.join("; "))));
+    }
+    *destination = Png::from_chunks(chunks);
+    Ok(report)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::image::{ColourType, Image};
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn image(sample: u16) -> Png {
+        Image::new(1, 1, 8, ColourType::Greyscale, vec![sample]).unwrap().to_png()
+    }
+
+    fn source() -> Png {
+        let mut png = image(0);
+        png.insert_chunk(1, chunk("pHYs", "phys")).unwrap();
+        png.insert_chunk(3, chunk("tEXt", "a\0b")).unwrap();
+        png.insert_chunk(4, chunk("vpAG", "unsafe")).unwrap();
+        png
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|c| c.chunk_type().to_string()).collect()
+    }
+
+    const DEFAULTS: TransplantOptions = TransplantOptions { allow_unsafe: false, replace_existing: false };
+
+    #[test]
+    fn test_copies_safe_chunks_in_place() {
+        let mut destination = image(1);
+        let report = transplant(&source(), &mut destination, None, &DEFAULTS).unwrap();
+        assert_eq!(types(&destination), vec!["IHDR", "pHYs", "IDAT", "tEXt", "IEND"]);
+        assert!(matches!(report[2].decision, Decision::Unsafe));
+    }
+
+    #[test]
+    fn test_unsafe_chunks() {
+        let mut destination = image(0);
+        transplant(&source(), &mut destination, None, &DEFAULTS).unwrap();
+        assert!(types(&destination).contains(&String::from("vpAG")));
+
+        let mut destination = image(1);
+        let options = TransplantOptions { allow_unsafe: true, replace_existing: false };
+        transplant(&source(), &mut destination, None, &options).unwrap();
+        assert!(types(&destination).contains(&String::from("vpAG")));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut destination = image(1);
+        destination.insert_chunk(1, chunk("pHYs", "other")).unwrap();
+        destination.insert_chunk(3, chunk("tEXt", "a\0b")).unwrap();
+        let report = transplant(&source(), &mut destination, None, &DEFAULTS).unwrap();
+        assert!(matches!(report[0].decision, Decision::AlreadyPresent));
+        assert!(matches!(report[1].decision, Decision::Duplicate));
+
+        let options = TransplantOptions { allow_unsafe: false, replace_existing: true };
+        transplant(&source(), &mut destination, None, &options).unwrap();
+        assert_eq!(destination.chunk_by_type("pHYs").unwrap().data(), b"phys");
+    }
+
+    #[test]
+    fn test_placement_follows_registry() {
+        // gAMA sits just before IDAT in a file without PLTE, but must come before PLTE
+        let mut destination = Image::new(1, 1, 8, ColourType::Indexed, vec![0]).unwrap().to_png();
+        destination.insert_chunk(1, Chunk::new(ChunkType::from_str("PLTE").unwrap(), vec![0, 0, 0])).unwrap();
+        let mut source = image(0);
+        source.insert_chunk(1, chunk("tEXt", "a\0b")).unwrap();
+        source.insert_chunk(2, Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0, 0, 177, 143])).unwrap();
+        let options = TransplantOptions { allow_unsafe: true, replace_existing: false };
+        transplant(&source, &mut destination, None, &options).unwrap();
+        assert_eq!(types(&destination), vec!["IHDR", "gAMA", "PLTE", "tEXt", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut destination = image(1);
+        let selector = ChunkSelector::from_str("tEXt").unwrap();
+        transplant(&source(), &mut destination, Some(&selector), &DEFAULTS).unwrap();
+        assert_eq!(types(&destination), vec!["IHDR", "IDAT", "tEXt", "IEND"]);
+    }
+}